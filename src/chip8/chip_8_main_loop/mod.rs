@@ -1,5 +1,7 @@
 mod opcodes;
 
+use super::Variant;
+
 struct Nibbles {
     first_bite: u8,
     nnn: usize,
@@ -10,9 +12,12 @@ struct Nibbles {
 }
 
 impl super::Chip8 {
-    pub fn tick(&mut self, keyboard: [bool; 16]) -> Option<&[[u8; 64]; 32]> {
+    pub fn tick(&mut self, keyboard: [bool; 16]) {
         self.resources.key = keyboard;
-        self.fontset_is_changed = false;
+
+        if self.is_vblank_waiting || self.halted.is_some() {
+            return;
+        }
 
         if self.resources.is_key_waiting {
             for (i, &valid_key) in self.resources.key.iter().enumerate() {
@@ -22,20 +27,34 @@ impl super::Chip8 {
                 }
             }
         } else {
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1;
-            }
-            if self.sound_timer > 0 {
-                self.sound_timer -= 1;
-            }
             self.fetch_opcode();
             let nib = self.decode_opcode();
             self.execute_opcode(&nib);
         }
+    }
+
+    // The frontend calls this at the end of every 60 Hz frame.
+    // Both timers count down here no matter how many instructions ran in between.
+    // Returns the framebuffer if anything was drawn since the previous vblank,
+    // so half-drawn states in the middle of a frame never reach the screen.
+    pub fn vblank(&mut self) -> Option<&[[u8; super::SCREEN_WIDTH]]> {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+
+        if self.is_vblank_waiting {
+            self.is_vblank_waiting = false;
+            self.vblank_passed = true;
+        }
+
         if !self.fontset_is_changed {
             None
         } else {
-            Some(&self.resources.gfx)
+            self.fontset_is_changed = false;
+            Some(self.framebuffer())
         }
     }
 
@@ -67,18 +86,28 @@ impl super::Chip8 {
 
     pub fn load_into_memory(&mut self, bytes: &[u8]) {
         for (i, &byte) in bytes.iter().enumerate() {
-            if self.pc + i > 4096 {
+            if self.pc + i >= self.memory.len() {
                 break;
             }
             self.memory[self.pc + i] = byte;
         }
+
+        if self.layout.variant == Variant::Hires && bytes.starts_with(&super::HIRES_SIGNATURE) {
+            self.pc = super::HIRES_ENTRY_POINT;
+            self.resources.screen_height = super::HIRES_SCREEN_HEIGHT;
+        }
     }
 
     fn execute_opcode(&mut self, nib: &Nibbles) {
         let nibbles = (nib.first_bite, nib.x, nib.y, nib.nibble);
         let mut is_jumped = false;
+        let chip8x = self.layout.variant == Variant::Chip8X;
         match nibbles {
             (0x0, 0x0, 0xE, 0x0) => self.exec_00e0(),
+            (0x0, 0x2, 0xA, 0x0) if chip8x => self.exec_02a0(),
+            (0x0, 0x2, 0x3, 0x0) if self.resources.screen_height == super::HIRES_SCREEN_HEIGHT => {
+                self.exec_0230()
+            }
             (0x0, 0x0, 0xE, 0xE) => {
                 self.exec_00ee();
                 is_jumped = true;
@@ -102,26 +131,41 @@ impl super::Chip8 {
             (0x8, _, _, 0x3) => self.exec_8xy3(nib.x, nib.y),
             (0x8, _, _, 0x4) => self.exec_8xy4(nib.x, nib.y),
             (0x8, _, _, 0x5) => self.exec_8xy5(nib.x, nib.y),
-            // 8xy6 <- interesting. Typo in cowgod's manual or ... ? TODO: check.
-            (0x8, _, _, 0x6) => self.exec_8xy6(nib.x),
+            // Whether 8xy6 and 8xye shift Vx or Vy depends on the interpreter, see `Quirks::shift_reads_vy`.
+            (0x8, _, _, 0x6) => self.exec_8xy6(nib.x, nib.y),
             (0x8, _, _, 0x7) => self.exec_8xy7(nib.x, nib.y),
-            (0x8, _, _, 0xE) => self.exec_8xye(nib.x),
+            (0x8, _, _, 0xE) => self.exec_8xye(nib.x, nib.y),
             (0x9, _, _, 0x0) => self.exec_9xy0(nib.x, nib.y),
             (0xA, _, _, _) => self.exec_annn(nib.nnn as u16),
+            // CHIP-8X has no BNNN jump.
+            (0xB, _, _, 0x0) if chip8x => self.exec_bxy0(nib.x, nib.y),
+            (0xB, _, _, _) if chip8x => self.exec_bxyn(nib.x, nib.y, nib.nibble as usize),
             (0xB, _, _, _) => {
-                self.exec_bnnn(nib.nnn as u16);
+                self.exec_bnnn(nib.x, nib.nnn as u16);
                 is_jumped = true
             }
             (0xC, _, _, _) => self.exec_cxkk(nib.x, nib.kk),
-            (0xD, _, _, _) => self.exec_dxyn(nib.x, nib.y, nib.nibble as usize),
+            (0xD, _, _, _) => {
+                if self.quirks.display_wait && !self.vblank_passed {
+                    // Stay on this instruction and run it again once the vblank has passed.
+                    self.is_vblank_waiting = true;
+                    is_jumped = true;
+                } else {
+                    self.vblank_passed = false;
+                    self.exec_dxyn(nib.x, nib.y, nib.nibble as usize);
+                }
+            }
             (0xE, _, 0x9, 0xE) => self.exec_ex9e(nib.x),
             (0xE, _, 0xA, 0x1) => self.exec_exa1(nib.x),
+            (0xE, _, 0xF, 0x2) if chip8x => self.exec_exf2(nib.x),
+            (0xE, _, 0xF, 0x5) if chip8x => self.exec_exf5(nib.x),
             (0xF, _, 0x0, 0x7) => self.exec_fx07(nib.x),
             (0xF, _, 0x0, 0xA) => self.exec_fx0a(nib.x),
             (0xF, _, 0x1, 0x5) => self.exec_fx15(nib.x),
             (0xF, _, 0x1, 0x8) => self.exec_fx18(nib.x),
             (0xF, _, 0x1, 0xE) => self.exec_fx1e(nib.x),
             (0xF, _, 0x2, 0x9) => self.exec_fx29(nib.x),
+            (0xF, _, 0x3, 0x0) => self.exec_fx30(nib.x),
             (0xF, _, 0x3, 0x3) => self.exec_fx33(nib.x),
             (0xF, _, 0x5, 0x5) => self.exec_fx55(nib.x),
             (0xF, _, 0x6, 0x5) => self.exec_fx65(nib.x),
@@ -133,3 +177,159 @@ impl super::Chip8 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::chip8::{
+        Chip8, Font, MemoryLayout, Quirks, HIRES_SCREEN_HEIGHT, MEMORY_SIZE, SCREEN_HEIGHT,
+    };
+
+    // A machine with the default layout running `program` from 0x200.
+    fn chip8(program: &[u8]) -> Chip8 {
+        let mut cpu = Chip8::new(MemoryLayout::default());
+        cpu.load_into_memory(program);
+        cpu
+    }
+
+    // A hi-res ROM: the 1260 signature, then `program` where the VIP interpreter would jump to at 0x2C0.
+    fn hires(program: &[u8]) -> Chip8 {
+        let mut rom = vec![0x12, 0x60];
+        rom.resize(0xC0, 0);
+        rom.extend_from_slice(program);
+        let mut cpu = Chip8::new(MemoryLayout::from_name("hires").unwrap());
+        cpu.load_into_memory(&rom);
+        cpu
+    }
+
+    fn run(cpu: &mut Chip8, instructions: usize) {
+        for _ in 0..instructions {
+            cpu.tick([false; 16]);
+        }
+    }
+
+    #[test]
+    fn load_store_increment_wraps_at_the_end_of_memory() {
+        // I = 0xFFE, store V0-VF, load them back from where I ended up.
+        let mut cpu = chip8(&[0xAF, 0xFE, 0xFF, 0x55, 0xFF, 0x65]);
+        cpu.set_quirks(Quirks {
+            load_store_increments_i: true,
+            ..Quirks::default()
+        });
+        cpu.v_registers[2] = 0x42;
+
+        run(&mut cpu, 2);
+        assert_eq!(cpu.memory[0x000], 0x42);
+        assert_eq!(cpu.i_reg, 0x100E);
+        run(&mut cpu, 1);
+        assert_eq!(cpu.i_reg, 0x101E);
+
+        // I itself wraps around instead of overflowing.
+        cpu.v_registers[2] = 0x42;
+        cpu.pc = 0x202;
+        cpu.i_reg = 0xFFF8;
+        run(&mut cpu, 1);
+        assert_eq!(cpu.i_reg, 0x0008);
+        assert_eq!(cpu.memory[(0xFFF8 + 2) % MEMORY_SIZE], 0x42);
+    }
+
+    #[test]
+    fn font_instructions_follow_the_layout() {
+        // ETI-660 programs at 0x600, with the font moved to 0x100.
+        let layout = MemoryLayout {
+            font_address: 0x100,
+            ..MemoryLayout::from_name("eti660").unwrap()
+        };
+        let mut cpu = Chip8::new(layout);
+        // I = small digit V0, then I = large digit V0.
+        cpu.load_into_memory(&[0xF0, 0x29, 0xF0, 0x30]);
+        let font = Font {
+            small: [0x11; 80],
+            large: [0x22; 160],
+        };
+        cpu.set_font(&font);
+        cpu.v_registers[0] = 3;
+
+        run(&mut cpu, 1);
+        assert_eq!(cpu.i_reg, 0x100 + 3 * 5);
+        assert_eq!(cpu.memory[cpu.i_reg as usize], 0x11);
+        run(&mut cpu, 1);
+        assert_eq!(cpu.i_reg, 0x100 + 80 + 3 * 10);
+        assert_eq!(cpu.memory[cpu.i_reg as usize], 0x22);
+    }
+
+    #[test]
+    fn hires_roms_start_at_0x2c0_with_64_rows() {
+        let cpu = hires(&[0x00, 0xE0]);
+        assert_eq!(cpu.pc, 0x2C0);
+        assert_eq!(cpu.framebuffer().len(), HIRES_SCREEN_HEIGHT);
+
+        // The same ROM under the standard layout is an ordinary one.
+        let mut cpu = Chip8::new(MemoryLayout::default());
+        cpu.load_into_memory(&[0x12, 0x60]);
+        assert_eq!(cpu.pc, 0x200);
+        assert_eq!(cpu.framebuffer().len(), SCREEN_HEIGHT);
+    }
+
+    #[test]
+    fn hires_00e0_clears_the_top_half_and_0230_everything() {
+        let mut cpu = hires(&[0x00, 0xE0, 0x02, 0x30]);
+        cpu.resources.gfx = [[1; 64]; HIRES_SCREEN_HEIGHT];
+
+        run(&mut cpu, 1);
+        assert!(cpu.framebuffer()[..32].iter().all(|row| *row == [0; 64]));
+        assert!(cpu.framebuffer()[32..].iter().all(|row| *row == [1; 64]));
+
+        run(&mut cpu, 1);
+        assert!(cpu.framebuffer().iter().all(|row| *row == [0; 64]));
+    }
+
+    #[test]
+    fn hires_sprites_wrap_at_row_64() {
+        // V0 = 0, V1 = 63, I = the two rows of data at 0x2CA, draw them at (V0, V1).
+        let mut cpu = hires(&[
+            0x60, 0x00, 0x61, 0x3F, 0xA2, 0xCA, 0xD0, 0x12, 0x12, 0xC8, 0xFF, 0xFF,
+        ]);
+        run(&mut cpu, 4);
+
+        let screen = cpu.framebuffer();
+        assert_eq!(screen[63][..8], [1; 8]);
+        assert_eq!(screen[0][..8], [1; 8]);
+        // Not at row 32, where a 32-row screen would wrap.
+        assert_eq!(screen[32], [0; 64]);
+        assert_eq!(screen[31], [0; 64]);
+    }
+
+    #[test]
+    fn chip8x_colours_zones_from_vx_and_vx1_in_vy() {
+        let mut cpu = Chip8::new(MemoryLayout::from_name("chip8x").unwrap());
+        // V0 = zones 0-1 across, V1 = the first four rows, V2 = colour 5, then B020 and loop.
+        cpu.load_into_memory(&[0x60, 0x10, 0x61, 0x00, 0x62, 0x05, 0xB0, 0x20, 0x13, 0x08]);
+        run(&mut cpu, 5);
+
+        // Everything else keeps the default red.
+        let colors = cpu.colors().unwrap();
+        assert_eq!(colors.foreground[0][..3], [5, 5, 1]);
+        assert_eq!(colors.foreground[3][..3], [5, 5, 1]);
+        assert_eq!(colors.foreground[4][..3], [1, 1, 1]);
+    }
+
+    #[test]
+    fn exf2_reads_the_second_keypad() {
+        // Colour the top left zone unless key 5 is down on the second keypad.
+        let rom = [
+            0x60, 0x00, 0x61, 0x00, 0x62, 0x05, 0x63, 0x05, 0xE3, 0xF2, 0xB0, 0x20, 0x13, 0x0C,
+        ];
+        let top_left = |second_keypad: [bool; 16]| {
+            let mut cpu = Chip8::new(MemoryLayout::from_name("chip8x").unwrap());
+            cpu.load_into_memory(&rom);
+            cpu.set_second_keypad(second_keypad);
+            run(&mut cpu, 7);
+            cpu.colors().unwrap().foreground[0][0]
+        };
+
+        let mut pressed = [false; 16];
+        pressed[5] = true;
+        assert_eq!(top_left([false; 16]), 5);
+        assert_eq!(top_left(pressed), 1);
+    }
+}
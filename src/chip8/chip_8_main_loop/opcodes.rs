@@ -1,13 +1,28 @@
-use crate::chip8::Chip8;
+use crate::chip8::{Chip8, MEMORY_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
 use rand::prelude::*;
 
 impl Chip8 {
     // CLS
+    // Only clears the top half in hi-res mode, the interpreter's routine still clears a single page.
     pub(super) fn exec_00e0(&mut self) {
-        for height in 0..32 {
-            for width in 0..64 {
-                self.resources.gfx[width][height] = 0;
-            }
+        for row in self.resources.gfx[..SCREEN_HEIGHT].iter_mut() {
+            *row = [0; SCREEN_WIDTH];
+        }
+        self.fontset_is_changed = true;
+    }
+
+    // Clears the whole 64x64 screen (hi-res CHIP-8).
+    pub(super) fn exec_0230(&mut self) {
+        for row in self.resources.gfx.iter_mut() {
+            *row = [0; SCREEN_WIDTH];
+        }
+        self.fontset_is_changed = true;
+    }
+
+    // Steps the background through blue, black, green and red (CHIP-8X).
+    pub(super) fn exec_02a0(&mut self) {
+        if let Some(colors) = &mut self.resources.colors {
+            colors.next_background();
         }
         self.fontset_is_changed = true;
     }
@@ -15,6 +30,13 @@ impl Chip8 {
     // The interpreter sets the program counter to the address at the top of the stack,
     // then subtracts 1 from the stack pointer.
     pub(super) fn exec_00ee(&mut self) {
+        if self.sp == 0 {
+            self.halted = Some(format!(
+                "stack underflow: return without a call at 0x{:03X}",
+                self.pc
+            ));
+            return;
+        }
         self.sp -= 1;
         self.pc = self.stack[self.sp] as usize;
     }
@@ -29,6 +51,14 @@ impl Chip8 {
     // then puts the current PC on the top of the stack.
     // The PC is then set to nnn.
     pub(super) fn exec_2nnn(&mut self, nnn: usize) {
+        if self.sp == self.stack.len() {
+            self.halted = Some(format!(
+                "stack overflow: more than {} nested calls at 0x{:03X}",
+                self.stack.len(),
+                self.pc
+            ));
+            return;
+        }
         self.stack[self.sp] = (self.pc + 2) as u16;
         self.sp += 1;
         self.pc = nnn;
@@ -118,7 +148,11 @@ impl Chip8 {
     }
 
     // If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
-    pub(super) fn exec_8xy6(&mut self, x: usize) {
+    // With the shift quirk Vy is shifted into Vx instead.
+    pub(super) fn exec_8xy6(&mut self, x: usize, y: usize) {
+        if self.quirks.shift_reads_vy {
+            self.v_registers[x] = self.v_registers[y];
+        }
         self.v_registers[0x0F] = self.v_registers[x] & 1;
         self.v_registers[x] >>= 1;
     }
@@ -136,7 +170,11 @@ impl Chip8 {
     }
 
     // If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
-    pub(super) fn exec_8xye(&mut self, x: usize) {
+    // With the shift quirk Vy is shifted into Vx instead.
+    pub(super) fn exec_8xye(&mut self, x: usize, y: usize) {
+        if self.quirks.shift_reads_vy {
+            self.v_registers[x] = self.v_registers[y];
+        }
         self.v_registers[0x0F] = (self.v_registers[x] & 0b1000_0000) >> 7;
         self.v_registers[x] <<= 1;
         // let mut val = self.v_registers[x] as u8;
@@ -163,11 +201,40 @@ impl Chip8 {
     }
 
     // The program counter is set to nnn plus the value of V0.
-    pub(super) fn exec_bnnn(&mut self, nnn: u16) {
-        let val = nnn + self.v_registers[0x00] as u16;
+    // With the jump quirk it's xnn plus Vx, where x is the top nibble of nnn.
+    pub(super) fn exec_bnnn(&mut self, x: usize, nnn: u16) {
+        let register = if self.quirks.jump_uses_vx { x } else { 0x00 };
+        let val = nnn + self.v_registers[register] as u16;
         self.pc = val as usize;
     }
 
+    // Colours a rectangle of 8x4 pixel zones in VY (CHIP-8X).
+    // The low nibble of VX is the leftmost zone, the high nibble how many more zones to the right.
+    // V(X+1) does the same vertically.
+    pub(super) fn exec_bxy0(&mut self, x: usize, y: usize) {
+        let vx = self.v_registers[x] as usize;
+        let vx1 = self.v_registers[(x + 1) & 0xF] as usize;
+        let color = self.v_registers[y];
+        if let Some(colors) = &mut self.resources.colors {
+            let columns = (vx & 0xF)..(vx & 0xF) + (vx >> 4) + 1;
+            let rows = (vx1 & 0xF) * 4..((vx1 & 0xF) + (vx1 >> 4) + 1) * 4;
+            colors.fill(columns, rows, color);
+        }
+        self.fontset_is_changed = true;
+    }
+
+    // Colours N rows of the 8 pixel wide zone at (VX, V(X+1)) in VY (CHIP-8X).
+    pub(super) fn exec_bxyn(&mut self, x: usize, y: usize, n: usize) {
+        let vx = self.v_registers[x] as usize;
+        let vx1 = self.v_registers[(x + 1) & 0xF] as usize;
+        let color = self.v_registers[y];
+        if let Some(colors) = &mut self.resources.colors {
+            let column = (vx % SCREEN_WIDTH) / 8;
+            colors.fill(column..column + 1, vx1..vx1 + n, color);
+        }
+        self.fontset_is_changed = true;
+    }
+
     // The interpreter generates a random number from 0 to 255, which is then ANDed with the value kk.
     // The results are stored in Vx.
     pub(super) fn exec_cxkk(&mut self, x: usize, kk: u8) {
@@ -187,10 +254,11 @@ impl Chip8 {
     // TODO: rewrite in more understandable way.
     pub(super) fn exec_dxyn(&mut self, x: usize, y: usize, n: usize) {
         self.v_registers[0x0F] = 0;
+        let height = self.resources.screen_height;
         for byte in 0..n {
-            let y = (self.v_registers[y] as usize + byte) % 32;
+            let y = (self.v_registers[y] as usize + byte) % height;
             for bit in 0..8 {
-                let x = (self.v_registers[x] as usize + bit) % 64;
+                let x = (self.v_registers[x] as usize + bit) % SCREEN_WIDTH;
                 let color = (self.memory[self.i_reg as usize + byte] >> (7 - bit)) & 1;
                 self.v_registers[0x0F] |= color & self.resources.gfx[y][x];
                 self.resources.gfx[y][x] ^= color;
@@ -213,6 +281,20 @@ impl Chip8 {
         }
     }
 
+    // Skips the next instruction if the key in Vx is down on the second keypad (CHIP-8X).
+    pub(super) fn exec_exf2(&mut self, x: usize) {
+        if self.resources.second_key[self.v_registers[x] as usize & 0xF] {
+            self.pc += 2;
+        }
+    }
+
+    // Skips the next instruction if the key in Vx is up on the second keypad (CHIP-8X).
+    pub(super) fn exec_exf5(&mut self, x: usize) {
+        if !self.resources.second_key[self.v_registers[x] as usize & 0xF] {
+            self.pc += 2;
+        }
+    }
+
     // The value of DT is placed into Vx.
     pub(super) fn exec_fx07(&mut self, x: usize) {
         self.v_registers[x] = self.delay_timer;
@@ -242,7 +324,13 @@ impl Chip8 {
 
     // The value of I is set to the location for the hexadecimal sprite corresponding to the value of Vx
     pub(super) fn exec_fx29(&mut self, x: usize) {
-        self.i_reg = (self.v_registers[x] as u16) * 5;
+        self.i_reg = (self.layout.font_address + self.v_registers[x] as usize * 5) as u16;
+    }
+
+    // I is set to the location of the large (8x10) sprite for the digit in Vx (SUPER-CHIP).
+    pub(super) fn exec_fx30(&mut self, x: usize) {
+        let large_font = self.layout.font_address + 80;
+        self.i_reg = (large_font + self.v_registers[x] as usize * 10) as u16;
     }
 
     // The interpreter takes the decimal value of Vx, and places the hundreds digit in memory at location in I,
@@ -254,16 +342,24 @@ impl Chip8 {
     }
 
     // The interpreter copies the values of registers V0 through Vx into memory, starting at the address in I.
+    // With the load/store quirk I ends up at I + x + 1. Addresses wrap around the end of memory,
+    // a loop that keeps storing walks I past it.
     pub(super) fn exec_fx55(&mut self, x: usize) {
         for i in 0..x + 1 {
-            self.memory[(self.i_reg as usize) + i as usize] = self.v_registers[i as usize];
+            self.memory[(self.i_reg as usize + i) % MEMORY_SIZE] = self.v_registers[i];
+        }
+        if self.quirks.load_store_increments_i {
+            self.i_reg = self.i_reg.wrapping_add(x as u16 + 1);
         }
     }
 
     //The interpreter reads values from memory starting at location I into registers V0 through Vx.
     pub(super) fn exec_fx65(&mut self, x: usize) {
         for i in 0..x + 1 {
-            self.v_registers[i as usize] = self.memory[self.i_reg as usize + i as usize];
+            self.v_registers[i] = self.memory[(self.i_reg as usize + i) % MEMORY_SIZE];
+        }
+        if self.quirks.load_store_increments_i {
+            self.i_reg = self.i_reg.wrapping_add(x as u16 + 1);
         }
     }
 }
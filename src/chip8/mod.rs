@@ -1,9 +1,27 @@
 pub mod chip_8_main_loop;
+pub const MEMORY_SIZE: usize = 4096;
+pub const SCREEN_WIDTH: usize = 64;
+pub const SCREEN_HEIGHT: usize = 32;
+// Hi-res CHIP-8 shows two pages of the normal screen on top of each other.
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
+
+// Hi-res ROMs start by jumping into the part of the interpreter they bring along,
+// which is COSMAC VIP machine code. The CHIP-8 program itself begins where that ends.
+const HIRES_SIGNATURE: [u8; 2] = [0x12, 0x60];
+const HIRES_ENTRY_POINT: usize = 0x2C0;
+
+// The order 02A0 steps through the CHIP-8X background colours in.
+const CHIP8X_BACKGROUNDS: [u8; 4] = [CHIP8X_BLUE, CHIP8X_BLACK, CHIP8X_GREEN, CHIP8X_RED];
+const CHIP8X_BLACK: u8 = 0;
+const CHIP8X_RED: u8 = 1;
+const CHIP8X_BLUE: u8 = 2;
+const CHIP8X_GREEN: u8 = 4;
+
 pub struct Chip8 {
-    stack: [u16; 16],
+    stack: Vec<u16>,
     sp: usize,
 
-    memory: [u8; 4096],
+    memory: [u8; MEMORY_SIZE],
     v_registers: [u8; 16],
 
     pc: usize, // TODO: try to change it to the reference in the future in order to make emulator more idimatic(?).
@@ -11,22 +29,196 @@ pub struct Chip8 {
     i_reg: u16,
 
     fontset_is_changed: bool,
+    is_vblank_waiting: bool,
+    vblank_passed: bool,
+    quirks: Quirks,
+    layout: MemoryLayout,
 
     delay_timer: u8,
     sound_timer: u8,
+    // Why the program stopped, e.g. a stack overflow. A halted machine ignores tick.
+    halted: Option<String>,
 
     resources: ExternalResources,
 }
 
 pub struct ExternalResources {
-    pub gfx: [[u8; 64]; 32],
+    // Always big enough for hi-res, only the first `screen_height` rows are in use.
+    pub gfx: [[u8; SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT],
+    pub screen_height: usize,
+    // Only CHIP-8X has colour.
+    pub colors: Option<ColorLayer>,
+    // The CHIP-8X's second keypad, read by EXF2/EXF5.
+    pub second_key: [bool; 16],
     pub key: [bool; 16],
     key_value: u8,
     is_key_waiting: bool,
     pub draw_flag: bool,
 }
 
-static CHIP8_FONTSET: [u8; 80] = [
+// Behaviours that differ between CHIP-8 interpreters. The defaults match the common modern ones.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Quirks {
+    // DXYN waits for the next vertical blank before drawing, like on the COSMAC VIP.
+    // Limits games to one sprite per frame, which is what many of them were tuned for.
+    pub display_wait: bool,
+    // 8XY6/8XYE shift VY into VX instead of shifting VX in place (COSMAC VIP).
+    pub shift_reads_vy: bool,
+    // FX55/FX65 leave I pointing past the last register they touched (COSMAC VIP).
+    pub load_store_increments_i: bool,
+    // BNNN jumps to XNN plus VX instead of NNN plus V0 (SUPER-CHIP).
+    pub jump_uses_vx: bool,
+}
+
+// Where things live in memory. Differs between the machines CHIP-8 ran on.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MemoryLayout {
+    // Where the ROM is loaded and execution starts. Everything below belongs to the interpreter.
+    pub program_start: usize,
+    // The built-in hex digits: the small font used by FX29, directly followed by the large one used by FX30.
+    pub font_address: usize,
+    // The first address programs can't use. The interpreter keeps its variables and the display above it.
+    pub memory_end: usize,
+    // How many nested subroutine calls fit on the stack.
+    pub stack_depth: usize,
+    pub variant: Variant,
+}
+
+// Interpreters that change more than where things are in memory.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Variant {
+    Chip8,
+    // ROMs starting with 1260 are hi-res CHIP-8 programs and run on a 64x64 screen.
+    Hires,
+    // Colour, a second keypad, and BNNN sets colours instead of jumping.
+    Chip8X,
+}
+
+impl Variant {
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Chip8 => "CHIP-8",
+            Variant::Hires => "hi-res CHIP-8",
+            Variant::Chip8X => "CHIP-8X",
+        }
+    }
+}
+
+// CHIP-8X colours, numbered like on the VP-590 colour board: 0 black, 1 red, 2 blue, 3 violet,
+// 4 green, 5 yellow, 6 aqua, 7 white. The framebuffer still only says which pixels are on,
+// this says which colour they're shown in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorLayer {
+    pub background: u8,
+    // One colour for every 8x1 pixel zone.
+    pub foreground: [[u8; SCREEN_WIDTH / 8]; SCREEN_HEIGHT],
+}
+
+impl Default for ColorLayer {
+    fn default() -> Self {
+        ColorLayer {
+            background: CHIP8X_BACKGROUNDS[0],
+            foreground: [[CHIP8X_RED; SCREEN_WIDTH / 8]; SCREEN_HEIGHT],
+        }
+    }
+}
+
+impl ColorLayer {
+    pub fn next_background(&mut self) {
+        let current = CHIP8X_BACKGROUNDS
+            .iter()
+            .position(|&c| c == self.background)
+            .unwrap_or(0);
+        self.background = CHIP8X_BACKGROUNDS[(current + 1) % CHIP8X_BACKGROUNDS.len()];
+    }
+
+    // Zones are clipped to the screen.
+    pub fn fill(
+        &mut self,
+        columns: std::ops::Range<usize>,
+        rows: std::ops::Range<usize>,
+        color: u8,
+    ) {
+        for row in self.foreground.iter_mut().take(rows.end).skip(rows.start) {
+            for zone in row.iter_mut().take(columns.end).skip(columns.start) {
+                *zone = color & 7;
+            }
+        }
+    }
+}
+
+impl Default for MemoryLayout {
+    fn default() -> Self {
+        MemoryLayout {
+            program_start: 0x200,
+            font_address: 0x050,
+            memory_end: MEMORY_SIZE,
+            stack_depth: 16,
+            variant: Variant::Chip8,
+        }
+    }
+}
+
+impl MemoryLayout {
+    pub const NAMES: [&'static str; 5] = ["standard", "vip", "eti660", "hires", "chip8x"];
+    // Real interpreters have 12 to 16 entries, anything deeper is a typo.
+    pub const MAX_STACK_DEPTH: usize = 64;
+
+    pub fn from_name(name: &str) -> Option<MemoryLayout> {
+        let standard = MemoryLayout::default();
+        // The COSMAC VIP keeps its stack and variables at 0xEA0 and the display at 0xF00.
+        let vip = MemoryLayout {
+            memory_end: 0xEA0,
+            stack_depth: 12,
+            ..standard
+        };
+        match name.to_lowercase().as_str() {
+            "standard" => Some(standard),
+            "vip" => Some(vip),
+            // The ETI-660 interpreter takes up the first 1.5 KB.
+            "eti660" => Some(MemoryLayout {
+                program_start: 0x600,
+                ..standard
+            }),
+            // Hi-res CHIP-8 runs on the VIP, with part of the interpreter loaded alongside the program.
+            "hires" => Some(MemoryLayout {
+                variant: Variant::Hires,
+                ..vip
+            }),
+            // The CHIP-8X interpreter is a page longer than the VIP's.
+            "chip8x" => Some(MemoryLayout {
+                program_start: 0x300,
+                variant: Variant::Chip8X,
+                ..vip
+            }),
+            _ => None,
+        }
+    }
+
+    // The largest ROM that fits.
+    pub fn rom_size_limit(&self) -> usize {
+        self.memory_end.saturating_sub(self.program_start)
+    }
+
+    // Anything that would put the font, the program or the stack outside of memory.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.memory_end > MEMORY_SIZE || self.program_start >= self.memory_end {
+            return Err("the program doesn't fit in memory".to_string());
+        }
+        if self.font_address + Font::SIZE > MEMORY_SIZE {
+            return Err("the font doesn't fit in memory".to_string());
+        }
+        if self.stack_depth == 0 || self.stack_depth > Self::MAX_STACK_DEPTH {
+            return Err(format!(
+                "the stack depth must be 1 to {}",
+                Self::MAX_STACK_DEPTH
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub static CHIP8_FONTSET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, //0
     0x20, 0x60, 0x20, 0x20, 0x70, //1
     0xF0, 0x10, 0xF0, 0x80, 0xF0, //2
@@ -45,30 +237,119 @@ static CHIP8_FONTSET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, //F
 ];
 
-impl Chip8 {
-    pub fn new() -> Chip8 {
-        let mut mem = [0u8; 4096];
-        for (i, &byte) in CHIP8_FONTSET.iter().enumerate() {
-            mem[i] = byte;
+// The SUPER-CHIP's 8x10 digits. It has no large letters, so A-F are blank.
+pub static CHIP8_LARGE_FONTSET: [u8; 160] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, //0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, //1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, //2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, //3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, //4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, //5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, //6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, //7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, //8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, //9
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //A
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //B
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //C
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //D
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //E
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //F
+];
+
+// The hex digit sprites the interpreter provides: 4x5 pixels (5 bytes) and 8x10 pixels (10 bytes) each.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Font {
+    pub small: [u8; 80],
+    pub large: [u8; 160],
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font {
+            small: CHIP8_FONTSET,
+            large: CHIP8_LARGE_FONTSET,
         }
-        Chip8 {
-            pc: 0x200,
+    }
+}
+
+impl Font {
+    // Bytes taken up in memory by both sizes.
+    pub const SIZE: usize = 80 + 160;
+}
+
+impl Chip8 {
+    pub fn new(layout: MemoryLayout) -> Chip8 {
+        let mut cpu = Chip8 {
+            pc: layout.program_start,
             opcode: 0,
             i_reg: 0,
             sp: 0,
-            memory: mem,
+            memory: [0u8; MEMORY_SIZE],
             v_registers: [0; 16],
-            stack: [0; 16],
+            stack: vec![0; layout.stack_depth],
             delay_timer: 0,
             sound_timer: 0,
+            halted: None,
             fontset_is_changed: false,
+            is_vblank_waiting: false,
+            vblank_passed: false,
+            quirks: Quirks::default(),
+            layout,
             resources: ExternalResources {
-                gfx: [[0; 64]; 32],
+                gfx: [[0; SCREEN_WIDTH]; HIRES_SCREEN_HEIGHT],
+                screen_height: SCREEN_HEIGHT,
+                colors: if layout.variant == Variant::Chip8X {
+                    Some(ColorLayer::default())
+                } else {
+                    None
+                },
+                second_key: [false; 16],
                 key: [false; 16],
                 draw_flag: false,
                 is_key_waiting: false,
                 key_value: 0,
             },
-        }
+        };
+        cpu.set_font(&Font::default());
+        cpu
+    }
+
+    // Replaces the digits in memory. Has to happen before the ROM is loaded, which may overlap them.
+    pub fn set_font(&mut self, font: &Font) {
+        let small = self.layout.font_address;
+        let large = small + font.small.len();
+        self.memory[small..large].copy_from_slice(&font.small);
+        self.memory[large..large + font.large.len()].copy_from_slice(&font.large);
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    // One row per line on screen: 32 rows, or 64 in hi-res mode.
+    pub fn framebuffer(&self) -> &[[u8; SCREEN_WIDTH]] {
+        &self.resources.gfx[..self.resources.screen_height]
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.layout.variant
+    }
+
+    pub fn colors(&self) -> Option<&ColorLayer> {
+        self.resources.colors.as_ref()
+    }
+
+    // The frontend sets this once per frame, next to the keys it passes to tick.
+    pub fn set_second_keypad(&mut self, keys: [bool; 16]) {
+        self.resources.second_key = keys;
+    }
+
+    pub fn halted(&self) -> Option<&str> {
+        self.halted.as_deref()
+    }
+
+    pub fn is_sound_playing(&self) -> bool {
+        self.sound_timer > 0
     }
 }
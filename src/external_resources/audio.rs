@@ -0,0 +1,161 @@
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use std::f32::consts::PI;
+
+const SAMPLE_RATE: i32 = 44_100;
+
+// How long the tone takes to fade in and out. Cutting a wave mid-cycle is what produces the click.
+const RAMP_SECONDS: f32 = 0.005;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Waveform> {
+        match name.to_lowercase().as_str() {
+            "square" => Some(Waveform::Square),
+            "sine" => Some(Waveform::Sine),
+            "triangle" => Some(Waveform::Triangle),
+            _ => None,
+        }
+    }
+
+    // Phase is in 0..1, the result is in -1..1.
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (phase * 2.0 * PI).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ToneSettings {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub volume: f32,
+}
+
+impl Default for ToneSettings {
+    fn default() -> Self {
+        ToneSettings {
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            volume: 0.25,
+        }
+    }
+}
+
+// Generates the buzzer tone sample by sample. It knows nothing about SDL,
+// so the same generator can be used for real-time playback and for offline rendering.
+pub struct ToneGenerator {
+    settings: ToneSettings,
+    sample_rate: f32,
+    phase: f32,
+    envelope: f32,
+    pub gate: bool,
+    pub muted: bool,
+}
+
+impl ToneGenerator {
+    pub fn new(settings: ToneSettings, sample_rate: u32) -> Self {
+        ToneGenerator {
+            settings,
+            sample_rate: sample_rate as f32,
+            phase: 0.0,
+            envelope: 0.0,
+            gate: false,
+            muted: false,
+        }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let target = if self.gate && !self.muted { 1.0 } else { 0.0 };
+        let step = 1.0 / (RAMP_SECONDS * self.sample_rate);
+        if self.envelope < target {
+            self.envelope = (self.envelope + step).min(target);
+        } else if self.envelope > target {
+            self.envelope = (self.envelope - step).max(target);
+        }
+
+        if self.envelope == 0.0 {
+            // Restart silent tones from the beginning of the cycle so they always fade in the same way.
+            self.phase = 0.0;
+            return 0.0;
+        }
+
+        let sample = self.settings.waveform.sample(self.phase);
+        self.phase = (self.phase + self.settings.frequency / self.sample_rate) % 1.0;
+
+        sample * self.settings.volume * self.envelope
+    }
+}
+
+struct Tone {
+    generator: ToneGenerator,
+    channels: usize,
+}
+
+impl AudioCallback for Tone {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for frame in out.chunks_mut(self.channels) {
+            let sample = self.generator.next_sample();
+            for channel in frame.iter_mut() {
+                *channel = sample;
+            }
+        }
+    }
+}
+
+pub struct Buzzer {
+    device: AudioDevice<Tone>,
+}
+
+impl Buzzer {
+    pub fn new(sdl_context: &sdl2::Sdl, settings: ToneSettings) -> Result<Self, String> {
+        let audio_subsys = sdl_context
+            .audio()
+            .map_err(|e| format!("cannot init audio subsystem: {}", e))?;
+
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: Some(512),
+        };
+
+        let device = audio_subsys
+            .open_playback(None, &desired, |spec| Tone {
+                generator: ToneGenerator::new(settings, spec.freq as u32),
+                channels: spec.channels as usize,
+            })
+            .map_err(|e| format!("cannot open audio device: {}", e))?;
+
+        // The device keeps running all the time and outputs silence while the gate is closed.
+        device.resume();
+
+        Ok(Buzzer { device })
+    }
+
+    // Called once per 60 Hz timer tick with the state of the sound timer.
+    pub fn set_playing(&mut self, playing: bool) {
+        self.device.lock().generator.gate = playing;
+    }
+
+    pub fn toggle_mute(&mut self) -> bool {
+        let mut tone = self.device.lock();
+        tone.generator.muted = !tone.generator.muted;
+        tone.generator.muted
+    }
+}
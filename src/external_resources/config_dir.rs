@@ -0,0 +1,16 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Where the emulator keeps things between runs: $XDG_CONFIG_HOME/chip8-emulator or ~/.config/chip8-emulator.
+// Creates the directory if needed. None if neither variable is set or the directory can't be created.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+    };
+
+    let dir = base.join("chip8-emulator");
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
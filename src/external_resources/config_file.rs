@@ -0,0 +1,24 @@
+use std::fs;
+use std::path::Path;
+
+// Reads one of the emulator's line-based files and hands every entry to `parse`.
+// Surrounding whitespace is trimmed, empty lines and lines starting with '#' are skipped.
+// Errors from `parse` get the path and line number in front, e.g. "hotkeys:3: invalid binding 'x'".
+pub fn for_each_line<P, F>(path: P, mut parse: F) -> Result<(), String>
+where
+    P: AsRef<Path>,
+    F: FnMut(&str) -> Result<(), String>,
+{
+    let path = path.as_ref();
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        parse(line).map_err(|e| format!("{}:{}: {}", path.display(), n + 1, e))?;
+    }
+    Ok(())
+}
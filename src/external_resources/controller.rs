@@ -0,0 +1,157 @@
+use sdl2::controller::{Axis, Button, GameController};
+use std::path::Path;
+
+use super::config_file::for_each_line;
+
+// Sticks report -32768..32767. Anything below this is treated as the stick resting in the center.
+const AXIS_DEAD_ZONE: i16 = 16_000;
+
+// Rumble is re-armed every frame while the buzzer is on,
+// so the duration only has to outlive a single frame.
+const RUMBLE_DURATION_MS: u32 = 100;
+
+// Maps controller buttons and stick directions to CHIP-8 keys.
+// The default layout fits most games: D-pad and left stick on 2/4/6/8, face button A on 5.
+pub struct ControllerMapping {
+    buttons: Vec<(Button, usize)>,
+    axes: Vec<(Axis, bool, usize)>,
+}
+
+impl Default for ControllerMapping {
+    fn default() -> Self {
+        ControllerMapping {
+            buttons: vec![
+                (Button::DPadUp, 0x2),
+                (Button::DPadLeft, 0x4),
+                (Button::DPadRight, 0x6),
+                (Button::DPadDown, 0x8),
+                (Button::A, 0x5),
+                (Button::B, 0x6),
+                (Button::X, 0x4),
+                (Button::Y, 0x8),
+            ],
+            axes: vec![
+                (Axis::LeftY, false, 0x2),
+                (Axis::LeftX, false, 0x4),
+                (Axis::LeftX, true, 0x6),
+                (Axis::LeftY, true, 0x8),
+            ],
+        }
+    }
+}
+
+impl ControllerMapping {
+    // The mapping file next to the ROM ("game.ch8" -> "game.ch8.pad"), if there is one.
+    pub fn for_rom(rom_path: &str) -> Result<Option<Self>, String> {
+        let path = format!("{}.pad", rom_path);
+        if Path::new(&path).exists() {
+            Self::load(&path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    // One binding per line: "<button or axis> = <hex key>", e.g. "dpup = 2", "leftx- = 4", "a = 5".
+    // Buttons and axes use SDL's mapping names. Axes need a trailing '+' or '-' for the direction.
+    // Empty lines and lines starting with '#' are ignored.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut mapping = ControllerMapping {
+            buttons: Vec::new(),
+            axes: Vec::new(),
+        };
+
+        for_each_line(path, |line| {
+            let (input, key) =
+                parse_binding(line).ok_or_else(|| format!("invalid binding '{}'", line))?;
+
+            if let Some(axis) = input.strip_suffix('+') {
+                let axis =
+                    Axis::from_string(axis).ok_or_else(|| format!("unknown axis '{}'", axis))?;
+                mapping.axes.push((axis, true, key));
+            } else if let Some(axis) = input.strip_suffix('-') {
+                let axis =
+                    Axis::from_string(axis).ok_or_else(|| format!("unknown axis '{}'", axis))?;
+                mapping.axes.push((axis, false, key));
+            } else {
+                let button = Button::from_string(input)
+                    .ok_or_else(|| format!("unknown button '{}'", input))?;
+                mapping.buttons.push((button, key));
+            }
+            Ok(())
+        })?;
+
+        Ok(mapping)
+    }
+
+    // Direction and button hints from the ROM database: "up", "down", "left", "right", "a" and "b".
+    pub fn from_hints(keys: &[(String, usize)]) -> Self {
+        let mut mapping = ControllerMapping {
+            buttons: Vec::new(),
+            axes: Vec::new(),
+        };
+
+        for (name, key) in keys {
+            let key = *key;
+            match name.as_str() {
+                "up" => {
+                    mapping.buttons.push((Button::DPadUp, key));
+                    mapping.axes.push((Axis::LeftY, false, key));
+                }
+                "down" => {
+                    mapping.buttons.push((Button::DPadDown, key));
+                    mapping.axes.push((Axis::LeftY, true, key));
+                }
+                "left" => {
+                    mapping.buttons.push((Button::DPadLeft, key));
+                    mapping.axes.push((Axis::LeftX, false, key));
+                }
+                "right" => {
+                    mapping.buttons.push((Button::DPadRight, key));
+                    mapping.axes.push((Axis::LeftX, true, key));
+                }
+                "a" => mapping.buttons.push((Button::A, key)),
+                "b" => mapping.buttons.push((Button::B, key)),
+                _ => {}
+            }
+        }
+
+        mapping
+    }
+
+    pub(super) fn apply(&self, pad: &GameController, chip8_keys: &mut [bool; 16]) {
+        for &(button, key) in &self.buttons {
+            if pad.button(button) {
+                chip8_keys[key] = true;
+            }
+        }
+
+        for &(axis, positive, key) in &self.axes {
+            let value = pad.axis(axis);
+            let pushed = if positive {
+                value > AXIS_DEAD_ZONE
+            } else {
+                value < -AXIS_DEAD_ZONE
+            };
+            if pushed {
+                chip8_keys[key] = true;
+            }
+        }
+    }
+}
+
+pub(super) fn rumble(pad: &mut GameController, enabled: bool) {
+    let intensity = if enabled { 0x8000 } else { 0 };
+    // Not every controller has rumble motors, and that's fine.
+    let _ = pad.set_rumble(intensity, intensity, RUMBLE_DURATION_MS);
+}
+
+fn parse_binding(line: &str) -> Option<(&str, usize)> {
+    let mut parts = line.splitn(2, '=');
+    let input = parts.next()?.trim();
+    let key = parts.next()?.trim();
+    let key = usize::from_str_radix(key.trim_start_matches("0x"), 16).ok()?;
+    if input.is_empty() || key > 0xF {
+        return None;
+    }
+    Some((input, key))
+}
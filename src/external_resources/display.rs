@@ -0,0 +1,72 @@
+use super::osd::Osd;
+use super::palette::Palette;
+use super::Hotkey;
+use crate::chip8::ColorLayer;
+
+// Anything that can show the CHIP-8 framebuffer.
+// `draw` hands over a new framebuffer, `present` puts the latest one on screen and is called once per 60 Hz frame.
+pub trait Display {
+    fn draw(&mut self, pixels: &[[u8; 64]]) -> Result<(), String>;
+
+    fn present(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Called after every emulated frame, however many of them run per `present`.
+    // Anything that has to follow emulated time rather than the host, like recordings, happens here.
+    fn end_frame(&mut self) {}
+
+    // Displays pick out the hotkeys they care about and ignore the rest.
+    fn hotkey(&mut self, _hotkey: Hotkey) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Only windows have a title, where the speed is shown.
+    fn set_title(&mut self, _title: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Only CHIP-8X has colours. Handed over with every new framebuffer.
+    fn set_colors(&mut self, _colors: Option<&ColorLayer>) {}
+
+    // Called when another ROM is loaded, with the palette to use for it and where to remember changes.
+    fn set_rom(&mut self, _rom_name: &str, _palette: Palette, _palette_path: String) {}
+
+    // Displays that can draw text over the picture. The rest simply don't show messages.
+    fn osd(&mut self) -> Option<&mut Osd> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DisplayKind {
+    Sdl,
+    Terminal,
+    Memory,
+}
+
+impl DisplayKind {
+    pub fn from_name(name: &str) -> Option<DisplayKind> {
+        match name {
+            "sdl" => Some(DisplayKind::Sdl),
+            "terminal" => Some(DisplayKind::Terminal),
+            "memory" => Some(DisplayKind::Memory),
+            _ => None,
+        }
+    }
+}
+
+// Keeps the last frame around instead of showing it. Handy for tests and headless runs.
+#[derive(Default)]
+pub struct MemoryDisplay {
+    pub frame: Option<Vec<[u8; 64]>>,
+    pub draw_count: usize,
+}
+
+impl Display for MemoryDisplay {
+    fn draw(&mut self, pixels: &[[u8; 64]]) -> Result<(), String> {
+        self.frame = Some(pixels.to_vec());
+        self.draw_count += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use super::image::Image;
+
+// How much darker scanlines and grid lines are than the pixel they cut through.
+const LINE_BRIGHTNESS: f32 = 0.5;
+
+// Post-processing that turns an image into a bigger one. Filters can be chained,
+// e.g. "scale2x,scanlines" doubles the screen with Scale2x and then adds scanlines on top.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {
+    // Scale2x and EPX are the same algorithm, found independently.
+    Scale2x,
+    Scale3x,
+    Xbr,
+    // Every pixel becomes a 3x3 block with a darker bottom row.
+    Scanlines,
+    // Every pixel becomes a 3x3 block with a darker bottom row and right column.
+    Grid,
+}
+
+impl Filter {
+    pub fn from_name(name: &str) -> Option<Filter> {
+        match name {
+            "scale2x" | "epx" => Some(Filter::Scale2x),
+            "scale3x" => Some(Filter::Scale3x),
+            "xbr" => Some(Filter::Xbr),
+            "scanlines" => Some(Filter::Scanlines),
+            "grid" => Some(Filter::Grid),
+            _ => None,
+        }
+    }
+
+    // Comma separated filter names, applied left to right.
+    pub fn parse_chain(names: &str) -> Option<Vec<Filter>> {
+        names
+            .split(',')
+            .map(|n| Filter::from_name(n.trim()))
+            .collect()
+    }
+
+    pub fn factor(self) -> usize {
+        match self {
+            Filter::Scale2x | Filter::Xbr => 2,
+            Filter::Scale3x | Filter::Scanlines | Filter::Grid => 3,
+        }
+    }
+
+    pub fn apply(self, image: &Image) -> Image {
+        match self {
+            Filter::Scale2x => scale2x(image),
+            Filter::Scale3x => scale3x(image),
+            Filter::Xbr => xbr(image),
+            Filter::Scanlines => lines(image, false),
+            Filter::Grid => lines(image, true),
+        }
+    }
+}
+
+pub fn apply_chain(chain: &[Filter], image: Image) -> Image {
+    chain
+        .iter()
+        .fold(image, |image, filter| filter.apply(&image))
+}
+
+// The size of a width x height image after the whole chain.
+pub fn chain_size(chain: &[Filter], width: usize, height: usize) -> (usize, usize) {
+    let factor: usize = chain.iter().map(|f| f.factor()).product();
+    (width * factor, height * factor)
+}
+
+// Neighbours around the current pixel E:
+// A B C
+// D E F
+// G H I
+struct Neighbours {
+    a: (u8, u8, u8),
+    b: (u8, u8, u8),
+    c: (u8, u8, u8),
+    d: (u8, u8, u8),
+    e: (u8, u8, u8),
+    f: (u8, u8, u8),
+    g: (u8, u8, u8),
+    h: (u8, u8, u8),
+    i: (u8, u8, u8),
+}
+
+fn neighbours(image: &Image, x: usize, y: usize) -> Neighbours {
+    let (x, y) = (x as isize, y as isize);
+    Neighbours {
+        a: image.get(x - 1, y - 1),
+        b: image.get(x, y - 1),
+        c: image.get(x + 1, y - 1),
+        d: image.get(x - 1, y),
+        e: image.get(x, y),
+        f: image.get(x + 1, y),
+        g: image.get(x - 1, y + 1),
+        h: image.get(x, y + 1),
+        i: image.get(x + 1, y + 1),
+    }
+}
+
+fn scale2x(image: &Image) -> Image {
+    let mut out = Image::new(image.width * 2, image.height * 2);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let n = neighbours(image, x, y);
+            let (e0, e1, e2, e3) = if n.b != n.h && n.d != n.f {
+                (
+                    if n.d == n.b { n.d } else { n.e },
+                    if n.b == n.f { n.f } else { n.e },
+                    if n.d == n.h { n.d } else { n.e },
+                    if n.h == n.f { n.f } else { n.e },
+                )
+            } else {
+                (n.e, n.e, n.e, n.e)
+            };
+            out.set(x * 2, y * 2, e0);
+            out.set(x * 2 + 1, y * 2, e1);
+            out.set(x * 2, y * 2 + 1, e2);
+            out.set(x * 2 + 1, y * 2 + 1, e3);
+        }
+    }
+    out
+}
+
+fn scale3x(image: &Image) -> Image {
+    let mut out = Image::new(image.width * 3, image.height * 3);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let n = neighbours(image, x, y);
+            let mut block = [n.e; 9];
+            if n.b != n.h && n.d != n.f {
+                let (a, b, c, d, e, f, g, h, i) = (n.a, n.b, n.c, n.d, n.e, n.f, n.g, n.h, n.i);
+                block[0] = if d == b { d } else { e };
+                block[1] = if (d == b && e != c) || (b == f && e != a) {
+                    b
+                } else {
+                    e
+                };
+                block[2] = if b == f { f } else { e };
+                block[3] = if (d == b && e != g) || (d == h && e != a) {
+                    d
+                } else {
+                    e
+                };
+                block[5] = if (b == f && e != i) || (h == f && e != c) {
+                    f
+                } else {
+                    e
+                };
+                block[6] = if d == h { d } else { e };
+                block[7] = if (d == h && e != i) || (h == f && e != g) {
+                    h
+                } else {
+                    e
+                };
+                block[8] = if h == f { f } else { e };
+            }
+            for (i, &color) in block.iter().enumerate() {
+                out.set(x * 3 + i % 3, y * 3 + i / 3, color);
+            }
+        }
+    }
+    out
+}
+
+// A simplified level 1 xBR working on the 3x3 neighbourhood only.
+// Each output corner checks which diagonal is the stronger edge and blends towards it,
+// which rounds off staircases instead of keeping them sharp like Scale2x.
+fn xbr(image: &Image) -> Image {
+    let mut out = Image::new(image.width * 2, image.height * 2);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let n = neighbours(image, x, y);
+            // Every corner is described by its vertical and horizontal neighbours, the diagonal one,
+            // the neighbours on the opposite sides and the two pixels the crossing diagonal runs through.
+            let corners = [
+                (n.b, n.d, n.a, n.h, n.f, n.c, n.g), // top left
+                (n.b, n.f, n.c, n.h, n.d, n.a, n.i), // top right
+                (n.h, n.d, n.g, n.b, n.f, n.a, n.i), // bottom left
+                (n.h, n.f, n.i, n.b, n.d, n.c, n.g), // bottom right
+            ];
+            for (corner, &(v, h, diagonal, v_opposite, h_opposite, side1, side2)) in
+                corners.iter().enumerate()
+            {
+                // Weight of the edge running across the corner vs. the one running into it.
+                let across = distance(n.e, side1) + distance(n.e, side2) + 4 * distance(v, h);
+                let into =
+                    distance(v, h_opposite) + distance(h, v_opposite) + 4 * distance(n.e, diagonal);
+                let color = if across < into {
+                    let closer = if distance(n.e, v) <= distance(n.e, h) {
+                        v
+                    } else {
+                        h
+                    };
+                    blend(n.e, closer)
+                } else {
+                    n.e
+                };
+                out.set(x * 2 + corner % 2, y * 2 + corner / 2, color);
+            }
+        }
+    }
+    out
+}
+
+fn lines(image: &Image, grid: bool) -> Image {
+    let mut out = Image::new(image.width * 3, image.height * 3);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let color = image.get(x as isize, y as isize);
+            for dy in 0..3 {
+                for dx in 0..3 {
+                    let on_line = dy == 2 || (grid && dx == 2);
+                    let color = if on_line { darken(color) } else { color };
+                    out.set(x * 3 + dx, y * 3 + dy, color);
+                }
+            }
+        }
+    }
+    out
+}
+
+// Perceptual-ish colour distance in YUV space, as used by the xBR family.
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let (r, g, bl) = (
+        a.0 as f32 - b.0 as f32,
+        a.1 as f32 - b.1 as f32,
+        a.2 as f32 - b.2 as f32,
+    );
+    let y = 0.299 * r + 0.587 * g + 0.114 * bl;
+    let u = -0.169 * r - 0.331 * g + 0.5 * bl;
+    let v = 0.5 * r - 0.419 * g - 0.081 * bl;
+    (48.0 * y.abs() + 7.0 * u.abs() + 6.0 * v.abs()) as u32
+}
+
+fn blend(a: (u8, u8, u8), b: (u8, u8, u8)) -> (u8, u8, u8) {
+    let mix = |a: u8, b: u8| ((a as u16 + b as u16) / 2) as u8;
+    (mix(a.0, b.0), mix(a.1, b.1), mix(a.2, b.2))
+}
+
+fn darken(color: (u8, u8, u8)) -> (u8, u8, u8) {
+    let dim = |c: u8| (c as f32 * LINE_BRIGHTNESS) as u8;
+    (dim(color.0), dim(color.1), dim(color.2))
+}
@@ -0,0 +1,179 @@
+use std::fs;
+use std::path::Path;
+
+use crate::chip8::{Font, CHIP8_FONTSET, CHIP8_LARGE_FONTSET};
+
+pub const NAMES: [&str; 5] = ["vip", "dream6800", "eti660", "schip", "octo"];
+
+// The COSMAC VIP's digits, read from its ROM.
+static VIP: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, //0
+    0x60, 0x20, 0x20, 0x20, 0x70, //1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, //2
+    0xF0, 0x10, 0x70, 0x10, 0xF0, //3
+    0x90, 0x90, 0xF0, 0x10, 0x10, //4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, //5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, //6
+    0xF0, 0x10, 0x10, 0x10, 0x10, //7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, //8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, //9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, //A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, //B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, //C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, //D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, //E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, //F
+];
+
+// Three pixels wide, which makes numbers in DREAM 6800 games look narrower.
+static DREAM_6800: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, //0
+    0x40, 0x40, 0x40, 0x40, 0x40, //1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, //2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, //3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, //4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, //5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, //6
+    0xE0, 0x20, 0x20, 0x20, 0x20, //7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, //8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, //9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, //A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, //B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, //C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, //D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, //E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, //F
+];
+
+static ETI_660: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, //0
+    0x20, 0x20, 0x20, 0x20, 0x20, //1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, //2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, //3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, //4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, //5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, //6
+    0xE0, 0x20, 0x20, 0x20, 0x20, //7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, //8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, //9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, //A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, //B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, //C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, //D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, //E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, //F
+];
+
+// Octo has large letters as well as digits.
+static OCTO_LARGE: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, //0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, //1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, //2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, //3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, //4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, //5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, //6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, //7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, //8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, //9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, //A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, //B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, //C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, //D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, //E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, //F
+];
+
+// A bundled font or a path to a font file.
+pub fn find(name: &str) -> Result<Font, String> {
+    // The machines without a large font keep the SUPER-CHIP one for FX30.
+    let small_only = |small: [u8; 80]| Font {
+        small,
+        large: CHIP8_LARGE_FONTSET,
+    };
+    match name {
+        "vip" => Ok(small_only(VIP)),
+        "dream6800" => Ok(small_only(DREAM_6800)),
+        "eti660" => Ok(small_only(ETI_660)),
+        // The SUPER-CHIP's small digits are the ones most emulators use, so they're the default.
+        "schip" => Ok(Font::default()),
+        "octo" => Ok(Font {
+            small: CHIP8_FONTSET,
+            large: OCTO_LARGE,
+        }),
+        _ if Path::new(name).exists() => load(name),
+        _ => Err(format!(
+            "unknown font: {} (expected {} or a font file)",
+            name,
+            NAMES.join(", ")
+        )),
+    }
+}
+
+// Raw sprite data: the 80 bytes of the small font, optionally followed by
+// the large font, either the SUPER-CHIP's 100 bytes of digits or 160 bytes with letters.
+pub fn load(path: &str) -> Result<Font, String> {
+    let bytes = fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    if !matches!(bytes.len(), 80 | 180 | 240) {
+        return Err(format!(
+            "{} is {} bytes, expected 80 (small font), 180 or 240 (small and large font)",
+            path,
+            bytes.len()
+        ));
+    }
+
+    let mut font = Font::default();
+    font.small.copy_from_slice(&bytes[..80]);
+    if bytes.len() > 80 {
+        font.large = [0; 160];
+        font.large[..bytes.len() - 80].copy_from_slice(&bytes[80..]);
+    }
+    Ok(font)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes `bytes` to a temporary font file and loads it.
+    fn load_bytes(name: &str, bytes: &[u8]) -> Result<Font, String> {
+        let path = std::env::temp_dir().join(format!("chip8-font-{}-{}", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let font = load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        font
+    }
+
+    #[test]
+    fn small_font_keeps_the_default_large_one() {
+        let font = load_bytes("small", &[0xAA; 80]).unwrap();
+        assert_eq!(font.small, [0xAA; 80]);
+        assert_eq!(font.large, CHIP8_LARGE_FONTSET);
+    }
+
+    #[test]
+    fn large_digits_only_leave_the_letters_blank() {
+        let mut bytes = vec![0xAA; 80];
+        bytes.extend_from_slice(&[0xBB; 100]);
+        let font = load_bytes("digits", &bytes).unwrap();
+        assert_eq!(font.small, [0xAA; 80]);
+        assert_eq!(font.large[..100], [0xBB; 100]);
+        assert_eq!(font.large[100..], [0; 60]);
+    }
+
+    #[test]
+    fn large_font_with_letters() {
+        let mut bytes = vec![0xAA; 80];
+        bytes.extend_from_slice(&[0xBB; 160]);
+        let font = load_bytes("letters", &bytes).unwrap();
+        assert_eq!(font.large, [0xBB; 160]);
+    }
+
+    #[test]
+    fn rejects_other_sizes() {
+        let error = load_bytes("odd", &[0; 100]).unwrap_err();
+        assert!(error.ends_with(
+            "is 100 bytes, expected 80 (small font), 180 or 240 (small and large font)"
+        ));
+    }
+}
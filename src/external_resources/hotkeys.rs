@@ -0,0 +1,80 @@
+use sdl2::keyboard::Keycode;
+
+use super::config_dir::config_dir;
+use super::config_file::for_each_line;
+use super::Hotkey;
+
+const FILE_NAME: &str = "hotkeys";
+
+// Which keyboard keys trigger which hotkeys in the SDL window.
+pub struct HotkeyBindings {
+    keys: Vec<(Keycode, Hotkey)>,
+}
+
+impl Default for HotkeyBindings {
+    fn default() -> Self {
+        HotkeyBindings {
+            keys: vec![
+                (Keycode::M, Hotkey::ToggleMute),
+                (Keycode::P, Hotkey::CyclePersistence),
+                (Keycode::O, Hotkey::CyclePalette),
+                (Keycode::F11, Hotkey::ToggleFullscreen),
+                (Keycode::F3, Hotkey::ToggleStats),
+                (Keycode::F9, Hotkey::ToggleRecording),
+                (Keycode::F12, Hotkey::Screenshot),
+                (Keycode::Space, Hotkey::Pause),
+                (Keycode::Backspace, Hotkey::Reset),
+                (Keycode::Equals, Hotkey::SpeedUp),
+                (Keycode::Minus, Hotkey::SpeedDown),
+                (Keycode::Tab, Hotkey::FastForward),
+                (Keycode::Period, Hotkey::FrameAdvance),
+            ],
+        }
+    }
+}
+
+impl HotkeyBindings {
+    // Reads "hotkeys" from the config directory, if there is one.
+    // One binding per line: "<hotkey> = <SDL key name>", e.g. "pause = P" or "fast-forward = Right Shift".
+    // A hotkey mentioned in the file loses its default keys, the rest keep theirs.
+    pub fn load() -> Result<Self, String> {
+        let mut bindings = Self::default();
+        let path = match config_dir() {
+            Some(dir) => dir.join(FILE_NAME),
+            None => return Ok(bindings),
+        };
+        if !path.exists() {
+            return Ok(bindings);
+        }
+
+        let mut custom = Vec::new();
+        for_each_line(&path, |line| {
+            let binding =
+                parse_binding(line).ok_or_else(|| format!("invalid binding '{}'", line))?;
+            custom.push(binding);
+            Ok(())
+        })?;
+
+        bindings.keys.retain(|&(key, hotkey)| {
+            !custom
+                .iter()
+                .any(|&(custom_key, custom_hotkey)| custom_key == key || custom_hotkey == hotkey)
+        });
+        bindings.keys.extend(custom);
+        Ok(bindings)
+    }
+
+    pub fn hotkey(&self, keycode: Keycode) -> Option<Hotkey> {
+        self.keys
+            .iter()
+            .find(|&&(key, _)| key == keycode)
+            .map(|&(_, hotkey)| hotkey)
+    }
+}
+
+fn parse_binding(line: &str) -> Option<(Keycode, Hotkey)> {
+    let mut parts = line.splitn(2, '=');
+    let hotkey = Hotkey::from_name(parts.next()?.trim())?;
+    let key = Keycode::from_name(parts.next()?.trim())?;
+    Some((key, hotkey))
+}
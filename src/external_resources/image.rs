@@ -0,0 +1,118 @@
+use super::palette::{Palette, CHIP8X_COLORS};
+use crate::chip8::ColorLayer;
+
+// An RGB picture of the screen, after palette and before anything SDL specific.
+// Filters, screenshots and recordings all work on this.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<(u8, u8, u8)>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![(0, 0, 0); width * height],
+        }
+    }
+
+    // Colours a framebuffer row by row. `levels` is the brightness of every pixel from the persistence filter,
+    // without it pixels are simply on or off. A CHIP-8X colour layer replaces the palette.
+    pub fn from_framebuffer(
+        pixels: &[u8],
+        width: usize,
+        levels: Option<&[f32]>,
+        palette: &Palette,
+        color_layer: Option<&ColorLayer>,
+    ) -> Self {
+        let colors = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| {
+                let level = match levels {
+                    Some(levels) => levels[i],
+                    None => (value != 0) as u8 as f32,
+                };
+                match color_layer {
+                    Some(layer) => chip8x_color(layer, i % width, i / width, level),
+                    None => color(palette, value, level),
+                }
+            })
+            .collect();
+
+        Image {
+            width,
+            height: pixels.len() / width,
+            pixels: colors,
+        }
+    }
+
+    // Nearest-neighbour scaling by a whole factor.
+    pub fn scaled(&self, factor: usize) -> Image {
+        if factor <= 1 {
+            return self.clone();
+        }
+
+        let mut out = Image::new(self.width * factor, self.height * factor);
+        for y in 0..out.height {
+            for x in 0..out.width {
+                out.set(x, y, self.pixels[(y / factor) * self.width + x / factor]);
+            }
+        }
+        out
+    }
+
+    // Packed R, G, B bytes, row by row.
+    pub fn to_rgb_bytes(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|&(r, g, b)| [r, g, b])
+            .collect()
+    }
+
+    // Out of range coordinates are clamped to the edge, which is what the scalers want.
+    pub fn get(&self, x: isize, y: isize) -> (u8, u8, u8) {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: (u8, u8, u8)) {
+        self.pixels[y * self.width + x] = color;
+    }
+}
+
+// Level is the pixel brightness between 0 (off) and 1 (fully lit).
+// Pixels that are fading out have no plane left, so they fade in the foreground colour.
+fn color(palette: &Palette, value: u8, level: f32) -> (u8, u8, u8) {
+    let background = palette.colors[0];
+    let foreground = if value == 0 {
+        palette.colors[1]
+    } else {
+        palette.colors[(value & 3) as usize]
+    };
+
+    mix(background, foreground, level)
+}
+
+fn chip8x_color(layer: &ColorLayer, x: usize, y: usize, level: f32) -> (u8, u8, u8) {
+    let background = CHIP8X_COLORS[layer.background as usize & 7];
+    let foreground = layer
+        .foreground
+        .get(y)
+        .and_then(|row| row.get(x / 8))
+        .map_or(background, |&c| CHIP8X_COLORS[c as usize & 7]);
+    mix(background, foreground, level)
+}
+
+fn mix(background: (u8, u8, u8), foreground: (u8, u8, u8), level: f32) -> (u8, u8, u8) {
+    let mix = |bg: u8, fg: u8| (bg as f32 + (fg as f32 - bg as f32) * level) as u8;
+    (
+        mix(background.0, foreground.0),
+        mix(background.1, foreground.1),
+        mix(background.2, foreground.2),
+    )
+}
@@ -2,12 +2,39 @@ use sdl2;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 
-impl super::Input {
-    pub fn poll(&mut self) -> Result<[bool; 16], String> {
+use super::controller;
+use super::input_source::InputSource;
+use super::Hotkey;
+
+impl InputSource for super::Input {
+    fn poll(&mut self) -> Result<[bool; 16], String> {
+        self.hotkeys.clear();
+
         for event in self.events.poll_iter() {
-            if let Event::Quit { .. } = event {
-                return Err("cannot handle keyboard event".to_string());
-            };
+            match event {
+                Event::Quit { .. } => return Err("cannot handle keyboard event".to_string()),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(hotkey) = self.bindings.hotkey(keycode) {
+                        self.hotkeys.push(hotkey);
+                    }
+                }
+                Event::DropFile { filename, .. } => self.dropped_file = Some(filename),
+                Event::ControllerDeviceAdded { which, .. } => {
+                    // A broken or unsupported pad shouldn't stop the game.
+                    if let Ok(pad) = self.controller_subsys.open(which) {
+                        self.controllers.push(pad);
+                    }
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.controllers
+                        .retain(|pad| pad.instance_id() != which as i32);
+                }
+                _ => {}
+            }
         }
 
         let keys: Vec<Keycode> = self
@@ -17,9 +44,19 @@ impl super::Input {
             .filter_map(Keycode::from_scancode)
             .collect();
 
+        self.held = keys
+            .iter()
+            .filter_map(|&key| self.bindings.hotkey(key))
+            .collect();
+
         let mut chip8_keys = [false; 16];
+        self.second_keypad = [false; 16];
 
         for key in keys {
+            if let Some(i) = second_keypad_index(key) {
+                self.second_keypad[i] = true;
+            }
+
             let index = match key {
                 Keycode::Num1 => Some(0x1),
                 Keycode::Num2 => Some(0x2),
@@ -42,6 +79,60 @@ impl super::Input {
                 chip8_keys[i] = true;
             }
         }
+        for pad in &self.controllers {
+            self.mapping.apply(pad, &mut chip8_keys);
+        }
+
         Ok(chip8_keys)
     }
+
+    fn second_keypad(&self) -> [bool; 16] {
+        self.second_keypad
+    }
+
+    fn hotkeys(&self) -> &[Hotkey] {
+        &self.hotkeys
+    }
+
+    fn dropped_file(&mut self) -> Option<String> {
+        self.dropped_file.take()
+    }
+
+    fn is_held(&self, hotkey: Hotkey) -> bool {
+        self.held.contains(&hotkey)
+    }
+
+    fn set_mapping(&mut self, mapping: controller::ControllerMapping) {
+        self.mapping = mapping;
+    }
+
+    // Keeps every connected controller rumbling while the buzzer is on.
+    fn rumble(&mut self, enabled: bool) {
+        for pad in &mut self.controllers {
+            controller::rumble(pad, enabled);
+        }
+    }
+}
+
+// The numeric keypad is the CHIP-8X's second keypad: its digits, then / * - + Enter . for A to F.
+fn second_keypad_index(key: Keycode) -> Option<usize> {
+    match key {
+        Keycode::Kp0 => Some(0x0),
+        Keycode::Kp1 => Some(0x1),
+        Keycode::Kp2 => Some(0x2),
+        Keycode::Kp3 => Some(0x3),
+        Keycode::Kp4 => Some(0x4),
+        Keycode::Kp5 => Some(0x5),
+        Keycode::Kp6 => Some(0x6),
+        Keycode::Kp7 => Some(0x7),
+        Keycode::Kp8 => Some(0x8),
+        Keycode::Kp9 => Some(0x9),
+        Keycode::KpDivide => Some(0xA),
+        Keycode::KpMultiply => Some(0xB),
+        Keycode::KpMinus => Some(0xC),
+        Keycode::KpPlus => Some(0xD),
+        Keycode::KpEnter => Some(0xE),
+        Keycode::KpPeriod => Some(0xF),
+        _ => None,
+    }
 }
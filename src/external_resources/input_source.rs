@@ -0,0 +1,85 @@
+use super::controller::ControllerMapping;
+use super::Hotkey;
+
+// Anything that can drive the CHIP-8 keypad. Polled once per frame.
+// An error means the source is done (window closed, script finished, ...) and the emulator should stop.
+pub trait InputSource {
+    fn poll(&mut self) -> Result<[bool; 16], String>;
+
+    // The CHIP-8X's second keypad as of the last poll. Sources without one never press it.
+    fn second_keypad(&self) -> [bool; 16] {
+        [false; 16]
+    }
+
+    // Hotkeys pressed since the previous poll.
+    fn hotkeys(&self) -> &[Hotkey] {
+        &[]
+    }
+
+    // A ROM dragged onto the window since the last call.
+    fn dropped_file(&mut self) -> Option<String> {
+        None
+    }
+
+    // For hotkeys that act while their key is down rather than once per press.
+    fn is_held(&self, _hotkey: Hotkey) -> bool {
+        false
+    }
+
+    fn rumble(&mut self, _enabled: bool) {}
+
+    // Called when another ROM is loaded. Only sources with game controllers have a mapping.
+    fn set_mapping(&mut self, _mapping: ControllerMapping) {}
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum InputKind {
+    Sdl,
+    Terminal,
+    Script(String),
+    Socket(u16),
+}
+
+impl InputKind {
+    // "sdl", "terminal", "script:<path>" or "socket:<port>".
+    pub fn from_name(name: &str) -> Option<InputKind> {
+        match name {
+            "sdl" => Some(InputKind::Sdl),
+            "terminal" => Some(InputKind::Terminal),
+            _ if name.starts_with("script:") => {
+                Some(InputKind::Script(name["script:".len()..].to_string()))
+            }
+            _ if name.starts_with("socket:") => {
+                name["socket:".len()..].parse().ok().map(InputKind::Socket)
+            }
+            _ => None,
+        }
+    }
+}
+
+// The usual hex keypad layout on a QWERTY keyboard:
+// 1 2 3 4      1 2 3 C
+// Q W E R  ->  4 5 6 D
+// A S D F      7 8 9 E
+// Z X C V      A 0 B F
+pub(super) fn keypad_index(c: char) -> Option<usize> {
+    match c.to_ascii_lowercase() {
+        '1' => Some(0x1),
+        '2' => Some(0x2),
+        '3' => Some(0x3),
+        '4' => Some(0xC),
+        'q' => Some(0x4),
+        'w' => Some(0x5),
+        'e' => Some(0x6),
+        'r' => Some(0xD),
+        'a' => Some(0x7),
+        's' => Some(0x8),
+        'd' => Some(0x9),
+        'f' => Some(0xE),
+        'z' => Some(0xA),
+        'x' => Some(0x0),
+        'c' => Some(0xB),
+        'v' => Some(0xF),
+        _ => None,
+    }
+}
@@ -0,0 +1,143 @@
+use super::config_dir::config_dir;
+use super::config_file::for_each_line;
+use crate::chip8::{MemoryLayout, Variant};
+
+const FILE_NAME: &str = "memory-layouts";
+
+// The layouts described in "memory-layouts" in the config directory.
+#[derive(Default)]
+struct Config {
+    layouts: Vec<(String, MemoryLayout)>,
+    // The "default = <name>" line.
+    default: Option<String>,
+}
+
+impl Config {
+    // One layout per line: "<name> = <program start> <font address> <memory end> <stack depth>",
+    // e.g. "dream6800 = 0x200 0x000 0x800 12". Numbers starting with 0x are hex.
+    // "default = <name>" picks the layout for ROMs nothing else decides for.
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        if let Some(name) = parse_default(line) {
+            self.default = Some(name.to_string());
+            return Ok(());
+        }
+        let (name, layout) =
+            parse_layout(line).ok_or_else(|| format!("invalid memory layout '{}'", line))?;
+        layout.validate()?;
+        self.layouts.push((name.to_string(), layout));
+        Ok(())
+    }
+
+    fn find(&self, name: &str) -> Option<MemoryLayout> {
+        self.layouts
+            .iter()
+            .find(|(layout_name, _)| layout_name.eq_ignore_ascii_case(name))
+            .map(|&(_, layout)| layout)
+    }
+}
+
+fn read_config() -> Result<Config, String> {
+    let mut config = Config::default();
+    if let Some(path) = config_dir().map(|dir| dir.join(FILE_NAME)) {
+        if path.exists() {
+            for_each_line(&path, |line| config.parse_line(line))?;
+        }
+    }
+    Ok(config)
+}
+
+// A built-in layout or one from "memory-layouts" in the config directory.
+// Layouts in the file win over built-in ones with the same name.
+pub fn lookup(name: &str) -> Result<MemoryLayout, String> {
+    if let Some(layout) = read_config()?.find(name) {
+        return Ok(layout);
+    }
+
+    MemoryLayout::from_name(name).ok_or_else(|| {
+        format!(
+            "unknown memory layout: {} (expected {} or one from {})",
+            name,
+            MemoryLayout::NAMES.join(", "),
+            FILE_NAME
+        )
+    })
+}
+
+// The layout named by the "default" line, if there is one.
+pub fn default_name() -> Result<Option<String>, String> {
+    Ok(read_config()?.default)
+}
+
+fn parse_default(line: &str) -> Option<&str> {
+    let mut parts = line.splitn(2, '=');
+    if !parts.next()?.trim().eq_ignore_ascii_case("default") {
+        return None;
+    }
+    let mut words = parts.next()?.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some(name), None) => Some(name),
+        _ => None,
+    }
+}
+
+fn parse_layout(line: &str) -> Option<(&str, MemoryLayout)> {
+    let mut parts = line.splitn(2, '=');
+    let name = parts.next()?.trim();
+    let numbers = parts
+        .next()?
+        .split_whitespace()
+        .map(parse_number)
+        .collect::<Option<Vec<usize>>>()?;
+    if name.is_empty() || numbers.len() != 4 {
+        return None;
+    }
+
+    Some((
+        name,
+        MemoryLayout {
+            program_start: numbers[0],
+            font_address: numbers[1],
+            memory_end: numbers[2],
+            stack_depth: numbers[3],
+            variant: Variant::Chip8,
+        },
+    ))
+}
+
+fn parse_number(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_layouts_and_the_default() {
+        let mut config = Config::default();
+        config
+            .parse_line("dream6800 = 0x200 0x000 0x800 12")
+            .unwrap();
+        config.parse_line("default = Dream6800").unwrap();
+
+        let layout = config.find("DREAM6800").unwrap();
+        assert_eq!(layout.memory_end, 0x800);
+        assert_eq!(layout.stack_depth, 12);
+        assert_eq!(config.default.as_deref(), Some("Dream6800"));
+        assert!(config.find("vip").is_none());
+    }
+
+    #[test]
+    fn rejects_layouts_that_dont_fit() {
+        let mut config = Config::default();
+        assert!(config.parse_line("default = vip eti660").is_err());
+        assert!(config.parse_line("big = 0x200 0x050 0x2000 16").is_err());
+        assert_eq!(
+            config.parse_line("deep = 0x200 0x050 0x1000 1000"),
+            Err("the stack depth must be 1 to 64".to_string())
+        );
+    }
+}
@@ -1,58 +1,244 @@
 use sdl2::pixels;
-use sdl2::render::Canvas;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Canvas, Texture};
 use sdl2::video::Window;
 
+use crate::chip8::ColorLayer;
+
+pub mod audio;
+pub mod config_dir;
+pub mod config_file;
+pub mod controller;
+pub mod display;
+pub mod filters;
+pub mod font;
+pub mod hotkeys;
+pub mod image;
 pub mod input;
+pub mod input_source;
+pub mod memory_layout;
+pub mod octo;
+pub mod octo_assembler;
+pub mod osd;
+pub mod palette;
+pub mod patch;
+pub mod persistence;
+pub mod recording;
 pub mod rom;
+pub mod rom_analysis;
+pub mod rom_browser;
+pub mod rom_database;
 pub mod screen;
+pub mod screenshot;
+pub mod script;
+pub mod socket;
+pub mod terminal;
+pub mod terminal_input;
+pub mod wav;
+pub mod window_state;
 
 const SCALE_FACTOR: u32 = 20;
 const SCREEN_WIDTH: u32 = 64 * SCALE_FACTOR;
 const SCREEN_HEIGHT: u32 = 32 * SCALE_FACTOR;
+const DEFAULT_FADE_FRAMES: u32 = 4;
+
+pub use audio::Buzzer;
+
+// Emulator controls that aren't part of the CHIP-8 keypad.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Hotkey {
+    ToggleMute,
+    CyclePersistence,
+    CyclePalette,
+    ToggleFullscreen,
+    Screenshot,
+    ToggleRecording,
+    ToggleStats,
+    Pause,
+    Reset,
+    SpeedUp,
+    SpeedDown,
+    // Only works while the key is held, see `InputSource::is_held`.
+    FastForward,
+    FrameAdvance,
+}
+
+impl Hotkey {
+    pub fn from_name(name: &str) -> Option<Hotkey> {
+        match name {
+            "mute" => Some(Hotkey::ToggleMute),
+            "persistence" => Some(Hotkey::CyclePersistence),
+            "palette" => Some(Hotkey::CyclePalette),
+            "fullscreen" => Some(Hotkey::ToggleFullscreen),
+            "screenshot" => Some(Hotkey::Screenshot),
+            "record" => Some(Hotkey::ToggleRecording),
+            "stats" => Some(Hotkey::ToggleStats),
+            "pause" => Some(Hotkey::Pause),
+            "reset" => Some(Hotkey::Reset),
+            "speed-up" => Some(Hotkey::SpeedUp),
+            "speed-down" => Some(Hotkey::SpeedDown),
+            "fast-forward" => Some(Hotkey::FastForward),
+            "frame-advance" => Some(Hotkey::FrameAdvance),
+            _ => None,
+        }
+    }
+}
 
 pub struct Input {
     events: sdl2::EventPump,
+    controller_subsys: sdl2::GameControllerSubsystem,
+    controllers: Vec<sdl2::controller::GameController>,
+    mapping: controller::ControllerMapping,
+    bindings: hotkeys::HotkeyBindings,
+    hotkeys: Vec<Hotkey>,
+    held: Vec<Hotkey>,
+    second_keypad: [bool; 16],
+    // A file dropped on the window that hasn't been picked up yet.
+    dropped_file: Option<String>,
 }
 
 pub struct Screen {
     canvas: Canvas<Window>,
+    texture: Texture,
+    frame: Vec<[u8; 64]>,
+    colors: Option<ColorLayer>,
+    persistence: persistence::PersistenceFilter,
+    fade_frames: u32,
+    palettes: palette::PaletteSet,
+    palette_path: Option<String>,
+    filters: Vec<filters::Filter>,
+    texture_size: (u32, u32),
+    integer_scale: bool,
+    // Last size and position outside of fullscreen, saved when the emulator closes.
+    windowed: window_state::WindowState,
+    // What was on screen after the last present, for screenshots.
+    last_image: image::Image,
+    rom_name: String,
+    screenshot_scale: usize,
+    recording: Option<recording::Recording>,
+    recording_format: recording::RecordingFormat,
+    osd: osd::Osd,
+}
+
+pub struct ScreenSettings {
+    pub title: String,
+    pub persistence: persistence::Persistence,
+    pub palette: palette::Palette,
+    // Where to remember the palette when it's changed at runtime.
+    pub palette_path: Option<String>,
+    pub filters: Vec<filters::Filter>,
+    // Only scale by whole multiples, which keeps every CHIP-8 pixel the same size.
+    pub integer_scale: bool,
+    // Screenshots are named after the ROM. Empty while no ROM is loaded.
+    pub rom_name: String,
+    pub screenshot_scale: usize,
+    pub recording_format: recording::RecordingFormat,
+    // Starts with the FPS/IPS counter on.
+    pub show_stats: bool,
 }
 
 impl Input {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Result<Self, String> {
+    pub fn new(
+        sdl_context: &sdl2::Sdl,
+        mapping: controller::ControllerMapping,
+        bindings: hotkeys::HotkeyBindings,
+    ) -> Result<Self, String> {
         let events = sdl_context
             .event_pump()
             .or_else(|e| Err(format!("cannot create SDL event: {}", e)))?;
+        let controller_subsys = sdl_context
+            .game_controller()
+            .map_err(|e| format!("cannot init game controller subsystem: {}", e))?;
 
-        Ok(Input { events })
+        // Controllers that are already plugged in are reported through ControllerDeviceAdded
+        // on the first poll, so they are opened the same way as hot-plugged ones.
+        Ok(Input {
+            events,
+            controller_subsys,
+            controllers: Vec::new(),
+            mapping,
+            bindings,
+            hotkeys: Vec::new(),
+            held: Vec::new(),
+            second_keypad: [false; 16],
+            dropped_file: None,
+        })
     }
 }
 
 impl Screen {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Result<Self, String> {
+    pub fn new(sdl_context: &sdl2::Sdl, settings: ScreenSettings) -> Result<Self, String> {
         let video_subsys = sdl_context.video().unwrap();
-        let window = video_subsys
-            .window(
-                "rust-sdl2_gfx: draw line & FPSManager",
-                SCREEN_WIDTH,
-                SCREEN_HEIGHT,
-            )
-            .position_centered()
-            .opengl()
+        let saved = window_state::WindowState::load();
+        let (width, height) = match saved {
+            Some(state) => (state.width, state.height),
+            None => (SCREEN_WIDTH, SCREEN_HEIGHT),
+        };
+
+        let mut builder = video_subsys.window(&settings.title, width, height);
+        builder.resizable();
+        match saved {
+            Some(state) => builder.position(state.x, state.y),
+            None => builder.position_centered(),
+        };
+
+        let window = builder
+            .build()
+            .map_err(|err| format!("cannot init video subsystem {}", err))?;
+
+        let (x, y) = window.position();
+        let (window_width, window_height) = window.size();
+        let windowed = window_state::WindowState {
+            x,
+            y,
+            width: window_width,
+            height: window_height,
+        };
+
+        // Scaling a 64x32 texture is nothing even for the software renderer, so no GPU is needed.
+        let mut canvas = window
+            .into_canvas()
+            .software()
             .build()
-            .or_else(|err| Err(format!("cannot init video subsystem {}", err)))?;
+            .map_err(|e| format!("can't create canvas: {}", e))?;
 
-        let canvas = window.into_canvas().build();
+        // The texture has the size of the filtered image, SDL only does the last nearest-neighbour step.
+        let (width, height) = filters::chain_size(&settings.filters, 64, 32);
+        let texture = canvas
+            .texture_creator()
+            .create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32)
+            .map_err(|e| format!("can't create texture: {}", e))?;
 
-        match canvas {
-            Ok(mut canv) => {
-                canv.set_draw_color(pixels::Color::RGB(0, 0, 0));
-                canv.clear();
-                canv.present();
+        let (r, g, b) = settings.palette.colors[0];
+        canvas.set_draw_color(pixels::Color::RGB(r, g, b));
+        canvas.clear();
+        canvas.present();
 
-                Ok(Screen { canvas: canv })
-            }
-            Err(e) => Err(format!("can't create canvas: {}", e)),
-        }
+        // Cycling through the modes at runtime starts fades with the configured length if there is one.
+        let fade_frames = match settings.persistence {
+            persistence::Persistence::Fade(frames, _) => frames,
+            _ => DEFAULT_FADE_FRAMES,
+        };
+
+        Ok(Screen {
+            canvas,
+            texture,
+            frame: vec![[0; 64]; 32],
+            colors: None,
+            persistence: persistence::PersistenceFilter::new(settings.persistence),
+            fade_frames,
+            palettes: palette::PaletteSet::new(settings.palette),
+            palette_path: settings.palette_path,
+            filters: settings.filters,
+            texture_size: (width as u32, height as u32),
+            integer_scale: settings.integer_scale,
+            windowed,
+            last_image: image::Image::new(width, height),
+            rom_name: settings.rom_name,
+            screenshot_scale: settings.screenshot_scale,
+            recording: None,
+            recording_format: settings.recording_format,
+            osd: osd::Osd::new(settings.show_stats),
+        })
     }
 }
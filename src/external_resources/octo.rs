@@ -0,0 +1,208 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use super::palette::{self, Palette};
+use crate::chip8::Quirks;
+
+const GIF_MAGIC: &[u8] = b"GIF8";
+
+// The settings Octo saves with a program. Octo's quirk flags are named after the SUPER-CHIP behaviour,
+// so "shiftQuirks: true" means shifting VX in place.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OctoOptions {
+    pub tickrate: Option<u32>,
+    pub background_color: Option<String>,
+    pub fill_color: Option<String>,
+    pub fill_color2: Option<String>,
+    pub blend_color: Option<String>,
+    pub shift_quirks: bool,
+    pub load_store_quirks: bool,
+    pub jump_quirks: bool,
+    pub v_blank_quirks: bool,
+}
+
+#[derive(Deserialize)]
+pub struct Cartridge {
+    // Octo source code, not an assembled ROM.
+    pub program: String,
+    #[serde(default)]
+    pub options: OctoOptions,
+}
+
+pub fn is_cartridge(bytes: &[u8]) -> bool {
+    bytes.starts_with(GIF_MAGIC)
+}
+
+impl Cartridge {
+    // Octo hides the program in the low nibble of every pixel's colour index, two pixels per byte,
+    // across all frames of the GIF. The palette repeats each label colour 16 times so the picture looks untouched.
+    // The data is a big-endian 32-bit length followed by that much JSON: {"program": ..., "options": {...}}.
+    pub fn decode(bytes: &[u8]) -> Result<Cartridge, String> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options
+            .read_info(bytes)
+            .map_err(|e| format!("cannot decode cartridge: {}", e))?;
+
+        let mut data = Vec::new();
+        while let Some(frame) = decoder
+            .read_next_frame()
+            .map_err(|e| format!("cannot decode cartridge: {}", e))?
+        {
+            for pair in frame.buffer.chunks_exact(2) {
+                data.push((pair[0] & 0xF) << 4 | (pair[1] & 0xF));
+            }
+        }
+
+        if data.len() < 4 {
+            return Err("cartridge has no data".to_string());
+        }
+        let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let json = data
+            .get(4..4 + size)
+            .ok_or_else(|| "cartridge data is cut short".to_string())?;
+
+        serde_json::from_slice(json).map_err(|e| format!("invalid cartridge data: {}", e))
+    }
+}
+
+impl OctoOptions {
+    pub fn quirks(&self) -> Quirks {
+        Quirks {
+            display_wait: self.v_blank_quirks,
+            shift_reads_vy: !self.shift_quirks,
+            load_store_increments_i: !self.load_store_quirks,
+            jump_uses_vx: self.jump_quirks,
+        }
+    }
+
+    pub fn palette(&self, name: &str) -> Option<Palette> {
+        let color = |c: &Option<String>| c.as_deref().and_then(palette::parse_color);
+        let background = color(&self.background_color)?;
+        let foreground = color(&self.fill_color)?;
+        Some(Palette {
+            name: name.to_string(),
+            colors: [
+                background,
+                foreground,
+                color(&self.fill_color2).unwrap_or(foreground),
+                color(&self.blend_color).unwrap_or(foreground),
+            ],
+        })
+    }
+
+    // The same settings as command line flags, for running the assembled program.
+    pub fn flags(&self) -> Vec<String> {
+        let quirks = self.quirks();
+        let mut flags = Vec::new();
+        if let Some(tickrate) = self.tickrate {
+            flags.push(format!("--ipf {}", tickrate));
+        }
+        if quirks.display_wait {
+            flags.push("--display-wait".to_string());
+        }
+        if quirks.shift_reads_vy {
+            flags.push("--shift-vy".to_string());
+        }
+        if quirks.load_store_increments_i {
+            flags.push("--load-store-increment".to_string());
+        }
+        if quirks.jump_uses_vx {
+            flags.push("--jump-vx".to_string());
+        }
+        flags
+    }
+}
+
+// Saves the source and the colours next to the cartridge, for working on the program in Octo,
+// and returns the flags that match its settings.
+// Only run by the unpack-octo subcommand, loading a ROM never writes files.
+pub fn unpack(path: &str, bytes: &[u8]) -> Result<String, String> {
+    let cartridge = Cartridge::decode(bytes)?;
+
+    let source_path = format!("{}.8o", path);
+    fs::write(&source_path, &cartridge.program)
+        .map_err(|e| format!("cannot write {}: {}", source_path, e))?;
+
+    let mut flags = cartridge.options.flags();
+    let name = Path::new(path)
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if let Some(palette) = cartridge.options.palette(&name) {
+        let palette_path = palette::rom_palette_path(path);
+        palette.save(&palette_path)?;
+        flags.push(format!("--palette {}", palette_path));
+    }
+
+    Ok(format!(
+        "The source was saved to {}; run it after assembling it with: {}",
+        source_path,
+        flags.join(" ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_resources::octo_assembler;
+    use std::borrow::Cow;
+
+    // A GIF with `data` in the low nibbles of a 16 x N picture, the way Octo saves cartridges.
+    fn gif_with(data: &[u8]) -> Vec<u8> {
+        let mut pixels: Vec<u8> = data.iter().flat_map(|b| vec![b >> 4, b & 0xF]).collect();
+        let height = pixels.len().div_ceil(16);
+        pixels.resize(16 * height, 0);
+
+        let palette: Vec<u8> = (0..16).flat_map(|i| vec![i * 16, i * 16, i * 16]).collect();
+        let mut gif = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut gif, 16, height as u16, &palette).unwrap();
+            let frame = gif::Frame {
+                width: 16,
+                height: height as u16,
+                buffer: Cow::Owned(pixels),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&frame).unwrap();
+        }
+        gif
+    }
+
+    fn cartridge(json: &str) -> Vec<u8> {
+        let mut data = (json.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(json.as_bytes());
+        gif_with(&data)
+    }
+
+    #[test]
+    fn decodes_the_program_and_its_settings() {
+        let bytes = cartridge(
+            r##"{"program": ": main\n  clear\n  loop again", "options": {"tickrate": 20,
+            "backgroundColor": "#000000", "fillColor": "#FF0000", "shiftQuirks": true}}"##,
+        );
+        assert!(is_cartridge(&bytes));
+
+        let cartridge = Cartridge::decode(&bytes).unwrap();
+        assert_eq!(cartridge.program, ": main\n  clear\n  loop again");
+        assert_eq!(cartridge.options.tickrate, Some(20));
+        assert!(!cartridge.options.quirks().shift_reads_vy);
+        assert!(cartridge.options.palette("test").is_some());
+        assert_eq!(
+            octo_assembler::assemble(&cartridge.program).unwrap(),
+            [0x00, 0xE0, 0x12, 0x02]
+        );
+    }
+
+    #[test]
+    fn rejects_cut_short_data() {
+        let mut data = 1000u32.to_be_bytes().to_vec();
+        data.extend_from_slice(br#"{"program": ""}"#);
+        assert_eq!(
+            Cartridge::decode(&gif_with(&data)).err(),
+            Some("cartridge data is cut short".to_string())
+        );
+    }
+}
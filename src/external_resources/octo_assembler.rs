@@ -0,0 +1,1248 @@
+use std::collections::{HashMap, VecDeque};
+
+// Octo programs start here, and XO-CHIP programs can fill the whole 64 KB address space.
+const PROGRAM_START: usize = 0x200;
+const ADDRESS_SPACE: usize = 0x10000;
+
+// Turns Octo source code into a ROM, for cartridges, which only carry the source.
+// Covers the language as Octo documents it: labels, constants, aliases, :calc expressions, macros,
+// string modes, structured if/loop/while, and the CHIP-8, SUPER-CHIP and XO-CHIP statements.
+// Debugger directives (:breakpoint, :monitor) are read and ignored.
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let mut assembler = Assembler::new(tokenize(source)?);
+    assembler.run().map_err(|e| match assembler.line {
+        0 => e,
+        line => format!("line {}: {}", line, e),
+    })?;
+    assembler.finish()
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    line: usize,
+    // A "quoted" string, which never means a name or a number.
+    string: bool,
+}
+
+fn tokenize(source: &str) -> Result<VecDeque<Token>, String> {
+    let mut tokens = VecDeque::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+
+    while let Some(&c) = chars.peek() {
+        if c == '\n' {
+            line += 1;
+            chars.next();
+        } else if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' {
+            while chars.peek().is_some_and(|&c| c != '\n') {
+                chars.next();
+            }
+        } else if c == '"' {
+            chars.next();
+            let start = line;
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    None => return Err(format!("line {}: missing closing quote", start)),
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => text.push('\n'),
+                        Some('r') => text.push('\r'),
+                        Some('t') => text.push('\t'),
+                        Some('0') => text.push('\0'),
+                        Some(c) => text.push(c),
+                        None => return Err(format!("line {}: missing closing quote", start)),
+                    },
+                    Some(c) => {
+                        if c == '\n' {
+                            line += 1;
+                        }
+                        text.push(c);
+                    }
+                }
+            }
+            tokens.push_back(Token {
+                text,
+                line: start,
+                string: true,
+            });
+        } else {
+            let mut text = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                text.push(c);
+                chars.next();
+            }
+            tokens.push_back(Token {
+                text,
+                line,
+                string: false,
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+fn number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn register_number(text: &str) -> Option<u8> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('v'), Some(digit), None) | (Some('V'), Some(digit), None) => {
+            digit.to_digit(16).map(|n| n as u8)
+        }
+        _ => None,
+    }
+}
+
+// Where a 12 or 16-bit address goes once a label defined further down is known.
+#[derive(Clone, Copy)]
+enum Fixup {
+    // The low 12 bits of an instruction, e.g. 1NNN or ANNN.
+    Instruction,
+    // Two bytes of a 16-bit address, for "i := long".
+    Long,
+    // The operand of "unpack-hi := nibble and bits 8-11" from :unpack.
+    UnpackHigh(u8),
+    // The operand of "unpack-lo := bits 0-7" from :unpack.
+    UnpackLow,
+    // The operand of "unpack-hi := bits 8-15" from ":unpack long".
+    UnpackLongHigh,
+}
+
+enum Target {
+    Known(usize),
+    Forward(String),
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Register(u8),
+    Value(u8),
+    None,
+}
+
+struct Macro {
+    arguments: Vec<String>,
+    body: Vec<Token>,
+    calls: usize,
+}
+
+// One alphabet character of a :stringmode and the code it expands to.
+struct StringModeEntry {
+    value: usize,
+    body: Vec<Token>,
+}
+
+struct Loop {
+    start: usize,
+    // The "jump out" slots of every while in the loop.
+    exits: Vec<usize>,
+}
+
+struct Assembler {
+    tokens: VecDeque<Token>,
+    // Line of the token being assembled, for errors.
+    line: usize,
+    memory: Vec<u8>,
+    written: Vec<bool>,
+    here: usize,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    string_modes: HashMap<String, HashMap<char, StringModeEntry>>,
+    fixups: Vec<(String, Fixup, usize, usize)>,
+    // The jump slots of open if ... begin blocks.
+    branches: Vec<usize>,
+    loops: Vec<Loop>,
+    // 0x200 holds "jump main" unless main comes first.
+    jump_to_main: bool,
+}
+
+impl Assembler {
+    fn new(tokens: VecDeque<Token>) -> Self {
+        let mut aliases = HashMap::new();
+        aliases.insert("unpack-hi".to_string(), 0x0);
+        aliases.insert("unpack-lo".to_string(), 0x1);
+        aliases.insert("compare-temp".to_string(), 0xF);
+
+        let mut assembler = Assembler {
+            tokens,
+            line: 0,
+            memory: vec![0; ADDRESS_SPACE],
+            written: vec![false; ADDRESS_SPACE],
+            here: PROGRAM_START,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases,
+            macros: HashMap::new(),
+            string_modes: HashMap::new(),
+            fixups: Vec::new(),
+            branches: Vec::new(),
+            loops: Vec::new(),
+            jump_to_main: true,
+        };
+        // Filled in at the end, once main's address is known.
+        assembler.here += 2;
+        assembler.written[PROGRAM_START] = true;
+        assembler.written[PROGRAM_START + 1] = true;
+        assembler
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        while let Some(token) = self.tokens.pop_front() {
+            self.line = token.line;
+            if token.string {
+                return Err(format!("unexpected string \"{}\"", token.text));
+            }
+            match number(&token.text) {
+                Some(value) => {
+                    let byte = self.check_byte(value, &token.text)?;
+                    self.byte(byte)?;
+                }
+                None => self.statement(&token.text)?,
+            }
+        }
+        self.line = 0;
+
+        if !self.branches.is_empty() {
+            return Err("'begin' without 'end'".to_string());
+        }
+        if !self.loops.is_empty() {
+            return Err("'loop' without 'again'".to_string());
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, String> {
+        let main = *self
+            .labels
+            .get("main")
+            .ok_or_else(|| "the program doesn't define a subroutine called 'main'".to_string())?;
+        if self.jump_to_main {
+            self.memory[PROGRAM_START] = 0x10 | (main >> 8) as u8 & 0xF;
+            self.memory[PROGRAM_START + 1] = main as u8;
+        }
+
+        for (name, fixup, address, line) in std::mem::take(&mut self.fixups) {
+            let value = *self
+                .labels
+                .get(&name)
+                .ok_or_else(|| format!("line {}: undefined name '{}'", line, name))?;
+            self.apply_fixup(fixup, address, value)
+                .map_err(|e| format!("line {}: {}", line, e))?;
+        }
+
+        let end = self
+            .written
+            .iter()
+            .rposition(|&written| written)
+            .map_or(PROGRAM_START, |last| last + 1);
+        Ok(self.memory[PROGRAM_START..end].to_vec())
+    }
+
+    fn statement(&mut self, token: &str) -> Result<(), String> {
+        match token {
+            ":" => {
+                let name = self.name()?;
+                // A main right at the start doesn't need the jump to it.
+                if name == "main" && self.jump_to_main && self.here == PROGRAM_START + 2 {
+                    self.jump_to_main = false;
+                    self.here = PROGRAM_START;
+                    self.written[PROGRAM_START] = false;
+                    self.written[PROGRAM_START + 1] = false;
+                }
+                self.define_label(name, self.here)?;
+            }
+            ":next" => {
+                let name = self.name()?;
+                self.define_label(name, self.here + 1)?;
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+            }
+            ":calc" => {
+                let name = self.name()?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = if self.peek() == Some("{") {
+                    let value = self.calc()?;
+                    self.check_range(value, 0.0, 15.0, "a register")? as u8
+                } else {
+                    self.register()?
+                };
+                self.aliases.insert(name, register);
+            }
+            ":byte" => {
+                let value = self.value()?;
+                let byte = self.check_byte(value, "value")?;
+                self.byte(byte)?;
+            }
+            ":org" => {
+                let value = self.value()?;
+                self.here =
+                    self.check_range(value, 0.0, (ADDRESS_SPACE - 1) as f64, "an address")?
+                        as usize;
+            }
+            ":call" => {
+                let target = self.target()?;
+                self.immediate(0x20, target)?;
+            }
+            ":unpack" => self.unpack()?,
+            ":macro" => self.define_macro()?,
+            ":stringmode" => self.define_string_mode()?,
+            ":assert" => {
+                let message = match self.tokens.front() {
+                    Some(token) if token.string => self.next()?.text,
+                    _ => "assertion failed".to_string(),
+                };
+                if self.calc()? == 0.0 {
+                    return Err(message);
+                }
+            }
+            ":breakpoint" | ":proto" => {
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            ";" | "return" => self.instruction(0x00, 0xEE)?,
+            "clear" => self.instruction(0x00, 0xE0)?,
+            "scroll-down" => {
+                let n = self.tiny()?;
+                self.instruction(0x00, 0xC0 | n)?;
+            }
+            "scroll-up" => {
+                let n = self.tiny()?;
+                self.instruction(0x00, 0xD0 | n)?;
+            }
+            "scroll-right" => self.instruction(0x00, 0xFB)?,
+            "scroll-left" => self.instruction(0x00, 0xFC)?,
+            "exit" => self.instruction(0x00, 0xFD)?,
+            "lores" => self.instruction(0x00, 0xFE)?,
+            "hires" => self.instruction(0x00, 0xFF)?,
+            "jump" => {
+                let target = self.target()?;
+                self.immediate(0x10, target)?;
+            }
+            "jump0" => {
+                let target = self.target()?;
+                self.immediate(0xB0, target)?;
+            }
+            "native" => {
+                let target = self.target()?;
+                self.immediate(0x00, target)?;
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.tiny()?;
+                self.instruction(0xD0 | x, y << 4 | n)?;
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    let op = if token == "save" { 0x2 } else { 0x3 };
+                    self.instruction(0x50 | x, y << 4 | op)?;
+                } else {
+                    let op = if token == "save" { 0x55 } else { 0x65 };
+                    self.instruction(0xF0 | x, op)?;
+                }
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.instruction(0xF0 | x, 0x75)?;
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.instruction(0xF0 | x, 0x85)?;
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.instruction(0xF0 | x, 0x33)?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                let op = match token {
+                    "delay" => 0x15,
+                    "buzzer" => 0x18,
+                    _ => 0x3A,
+                };
+                self.instruction(0xF0 | x, op)?;
+            }
+            "plane" => {
+                let n = self.tiny()?;
+                self.instruction(0xF0 | n, 0x01)?;
+            }
+            "audio" => self.instruction(0xF0, 0x02)?,
+            "i" => self.index_statement()?,
+            "if" => {
+                let (x, op, operand) = self.condition()?;
+                match self.next()?.text.as_str() {
+                    "then" => self.conditional(x, &op, operand, false)?,
+                    "begin" => {
+                        self.conditional(x, &op, operand, true)?;
+                        self.branches.push(self.here);
+                        self.instruction(0x10, 0x00)?;
+                    }
+                    other => return Err(format!("expected 'then' or 'begin', got '{}'", other)),
+                }
+            }
+            "else" => {
+                let branch = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| "'else' without 'begin'".to_string())?;
+                let jump = self.here;
+                self.instruction(0x10, 0x00)?;
+                self.patch_jump(branch, self.here);
+                self.branches.push(jump);
+            }
+            "end" => {
+                let branch = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| "'end' without 'begin'".to_string())?;
+                self.patch_jump(branch, self.here);
+            }
+            "loop" => self.loops.push(Loop {
+                start: self.here,
+                exits: Vec::new(),
+            }),
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err("'while' outside of a loop".to_string());
+                }
+                let (x, op, operand) = self.condition()?;
+                self.conditional(x, &op, operand, true)?;
+                let exit = self.here;
+                self.instruction(0x10, 0x00)?;
+                if let Some(current) = self.loops.last_mut() {
+                    current.exits.push(exit);
+                }
+            }
+            "again" => {
+                let finished = self
+                    .loops
+                    .pop()
+                    .ok_or_else(|| "'again' without 'loop'".to_string())?;
+                self.immediate(0x10, Target::Known(finished.start))?;
+                for exit in finished.exits {
+                    self.patch_jump(exit, self.here);
+                }
+            }
+            "then" | "begin" | "{" | "}" => return Err(format!("unexpected '{}'", token)),
+            _ if self.is_register(token) => self.register_statement(token)?,
+            _ if self.macros.contains_key(token) => self.expand_macro(token)?,
+            _ if self.string_modes.contains_key(token) => self.expand_string_mode(token)?,
+            _ if token.starts_with(':') => return Err(format!("unknown directive '{}'", token)),
+            // Anything else is a subroutine call.
+            _ => {
+                let target = self.target_of(token)?;
+                self.immediate(0x20, target)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, token: &str) -> Result<(), String> {
+        let x = self.register_of(token)?;
+        let op = self.next()?.text;
+        let rhs_is_register = self.peek().is_some_and(|text| self.is_register(text));
+        match op.as_str() {
+            ":=" if rhs_is_register => {
+                let y = self.register()?;
+                self.instruction(0x80 | x, y << 4)
+            }
+            ":=" => match self.peek() {
+                Some("random") => {
+                    self.next()?;
+                    let mask = self.short()?;
+                    self.instruction(0xC0 | x, mask)
+                }
+                Some("key") => {
+                    self.next()?;
+                    self.instruction(0xF0 | x, 0x0A)
+                }
+                Some("delay") => {
+                    self.next()?;
+                    self.instruction(0xF0 | x, 0x07)
+                }
+                _ => {
+                    let value = self.short()?;
+                    self.instruction(0x60 | x, value)
+                }
+            },
+            "+=" if rhs_is_register => {
+                let y = self.register()?;
+                self.instruction(0x80 | x, y << 4 | 0x4)
+            }
+            "+=" => {
+                let value = self.short()?;
+                self.instruction(0x70 | x, value)
+            }
+            "-=" if rhs_is_register => {
+                let y = self.register()?;
+                self.instruction(0x80 | x, y << 4 | 0x5)
+            }
+            "-=" => {
+                let value = self.short()?;
+                self.instruction(0x70 | x, value.wrapping_neg())
+            }
+            "=-" | "|=" | "&=" | "^=" | ">>=" | "<<=" => {
+                let y = self.register()?;
+                let n = match op.as_str() {
+                    "|=" => 0x1,
+                    "&=" => 0x2,
+                    "^=" => 0x3,
+                    ">>=" => 0x6,
+                    "=-" => 0x7,
+                    _ => 0xE,
+                };
+                self.instruction(0x80 | x, y << 4 | n)
+            }
+            _ => Err(format!("unknown operator '{}'", op)),
+        }
+    }
+
+    fn index_statement(&mut self) -> Result<(), String> {
+        match self.next()?.text.as_str() {
+            ":=" => match self.peek() {
+                Some("hex") | Some("bighex") => {
+                    let op = if self.next()?.text == "hex" {
+                        0x29
+                    } else {
+                        0x30
+                    };
+                    let x = self.register()?;
+                    self.instruction(0xF0 | x, op)
+                }
+                Some("long") => {
+                    self.next()?;
+                    let target = self.target()?;
+                    self.instruction(0xF0, 0x00)?;
+                    match target {
+                        Target::Known(address) => {
+                            self.byte((address >> 8) as u8)?;
+                            self.byte(address as u8)
+                        }
+                        Target::Forward(name) => {
+                            self.fixups.push((name, Fixup::Long, self.here, self.line));
+                            self.byte(0)?;
+                            self.byte(0)
+                        }
+                    }
+                }
+                _ => {
+                    let target = self.target()?;
+                    self.immediate(0xA0, target)
+                }
+            },
+            "+=" => {
+                let x = self.register()?;
+                self.instruction(0xF0 | x, 0x1E)
+            }
+            op => Err(format!("unknown operator 'i {}'", op)),
+        }
+    }
+
+    // ":unpack <nibble> <address>" loads the nibble and a 12-bit address into unpack-hi and unpack-lo,
+    // ":unpack long <address>" a 16-bit one.
+    fn unpack(&mut self) -> Result<(), String> {
+        let high = self.aliases["unpack-hi"];
+        let low = self.aliases["unpack-lo"];
+        let high_fixup = if self.peek() == Some("long") {
+            self.next()?;
+            Fixup::UnpackLongHigh
+        } else {
+            Fixup::UnpackHigh(self.tiny()?)
+        };
+        let target = self.target()?;
+
+        let start = self.here;
+        self.instruction(0x60 | high, 0)?;
+        self.instruction(0x60 | low, 0)?;
+        match target {
+            Target::Known(address) => {
+                self.apply_fixup(high_fixup, start, address)?;
+                self.apply_fixup(Fixup::UnpackLow, start + 2, address)?;
+            }
+            Target::Forward(name) => {
+                self.fixups
+                    .push((name.clone(), high_fixup, start, self.line));
+                self.fixups
+                    .push((name, Fixup::UnpackLow, start + 2, self.line));
+            }
+        }
+        Ok(())
+    }
+
+    // The operands of a condition, read before anything is written, so "then" and "begin" can decide
+    // which way round it goes.
+    fn condition(&mut self) -> Result<(u8, String, Operand), String> {
+        let x = self.register()?;
+        let op = self.next()?.text;
+        let operand = match op.as_str() {
+            "key" | "-key" => Operand::None,
+            "==" | "!=" | "<" | ">" | "<=" | ">=" => {
+                if self.peek().is_some_and(|text| self.is_register(text)) {
+                    Operand::Register(self.register()?)
+                } else {
+                    Operand::Value(self.short()?)
+                }
+            }
+            _ => return Err(format!("expected a comparison, got '{}'", op)),
+        };
+        Ok((x, op, operand))
+    }
+
+    // Writes the skip that jumps over the next instruction when the condition is false,
+    // or when it's true if `negated`. <, >, <= and >= go through compare-temp (VF).
+    fn conditional(
+        &mut self,
+        x: u8,
+        op: &str,
+        operand: Operand,
+        negated: bool,
+    ) -> Result<(), String> {
+        let op = if negated {
+            match op {
+                "==" => "!=",
+                "!=" => "==",
+                "<" => ">=",
+                ">" => "<=",
+                "<=" => ">",
+                ">=" => "<",
+                "key" => "-key",
+                _ => "key",
+            }
+        } else {
+            op
+        };
+        let temp = self.aliases["compare-temp"];
+        match (op, operand) {
+            ("==", Operand::Register(y)) => self.instruction(0x90 | x, y << 4),
+            ("==", Operand::Value(n)) => self.instruction(0x40 | x, n),
+            ("!=", Operand::Register(y)) => self.instruction(0x50 | x, y << 4),
+            ("!=", Operand::Value(n)) => self.instruction(0x30 | x, n),
+            ("key", _) => self.instruction(0xE0 | x, 0xA1),
+            ("-key", _) => self.instruction(0xE0 | x, 0x9E),
+            (_, operand) => {
+                match operand {
+                    Operand::Register(y) => self.instruction(0x80 | temp, y << 4)?,
+                    Operand::Value(n) => self.instruction(0x60 | temp, n)?,
+                    Operand::None => unreachable!(),
+                }
+                let (subtract, skip) = match op {
+                    ">" => (0x5, 0x30),
+                    "<" => (0x7, 0x30),
+                    ">=" => (0x7, 0x40),
+                    _ => (0x5, 0x40),
+                };
+                self.instruction(0x80 | temp, x << 4 | subtract)?;
+                self.instruction(skip | temp, 1)
+            }
+        }
+    }
+
+    fn define_macro(&mut self) -> Result<(), String> {
+        let name = self.name()?;
+        let mut arguments = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token.text == "{" && !token.string {
+                break;
+            }
+            arguments.push(token.text);
+        }
+        let body = self.block()?;
+        self.macros.insert(
+            name,
+            Macro {
+                arguments,
+                body,
+                calls: 0,
+            },
+        );
+        Ok(())
+    }
+
+    fn expand_macro(&mut self, name: &str) -> Result<(), String> {
+        let count = self.macros[name].arguments.len();
+        let mut values = Vec::new();
+        for _ in 0..count {
+            values.push(self.next()?);
+        }
+
+        let line = self.line;
+        let definition = self.macros.get_mut(name).expect("checked by the caller");
+        let calls = definition.calls;
+        definition.calls += 1;
+        let expansion: Vec<Token> = definition
+            .body
+            .iter()
+            .map(|token| {
+                if token.string {
+                    return token.clone();
+                }
+                if let Some(i) = definition.arguments.iter().position(|a| *a == token.text) {
+                    return values[i].clone();
+                }
+                if token.text == "CALLS" {
+                    return number_token(calls, line);
+                }
+                token.clone()
+            })
+            .collect();
+        for token in expansion.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    // ":stringmode <name> "<alphabet>" { body }": using the name on a string expands the body
+    // for every character, with CHAR its code, INDEX its position in the string
+    // and VALUE its position in the alphabet.
+    fn define_string_mode(&mut self) -> Result<(), String> {
+        let name = self.name()?;
+        let alphabet = self.next()?;
+        if !alphabet.string {
+            return Err(format!(
+                "expected an alphabet string, got '{}'",
+                alphabet.text
+            ));
+        }
+        self.expect("{")?;
+        let body = self.block()?;
+
+        let mode = self.string_modes.entry(name).or_default();
+        for (value, c) in alphabet.text.chars().enumerate() {
+            mode.insert(
+                c,
+                StringModeEntry {
+                    value,
+                    body: body.clone(),
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn expand_string_mode(&mut self, name: &str) -> Result<(), String> {
+        let text = self.next()?;
+        if !text.string {
+            return Err(format!("{} needs a string, got '{}'", name, text.text));
+        }
+
+        let mut expansion = Vec::new();
+        for (index, c) in text.text.chars().enumerate() {
+            let entry = self.string_modes[name]
+                .get(&c)
+                .ok_or_else(|| format!("string mode {} has no character '{}'", name, c))?;
+            for token in &entry.body {
+                expansion.push(match token.text.as_str() {
+                    _ if token.string => token.clone(),
+                    "CHAR" => number_token(c as usize, self.line),
+                    "INDEX" => number_token(index, self.line),
+                    "VALUE" => number_token(entry.value, self.line),
+                    _ => token.clone(),
+                });
+            }
+        }
+        for token in expansion.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    // Tokens up to the "}" matching an already read "{".
+    fn block(&mut self) -> Result<Vec<Token>, String> {
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self.next()?;
+            if !token.string {
+                match token.text.as_str() {
+                    "{" => depth += 1,
+                    "}" => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Ok(body);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            body.push(token);
+        }
+    }
+
+    // "{ expression }". Octo's expressions have no precedence and group from the right,
+    // so "2 * 3 + 1" is 8. Parentheses group as usual.
+    fn calc(&mut self) -> Result<f64, String> {
+        self.expect("{")?;
+        let value = self.expression()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    fn expression(&mut self) -> Result<f64, String> {
+        let unary = match self.peek() {
+            Some(op) if is_unary(op) => Some(self.next()?.text),
+            _ => None,
+        };
+        if let Some(op) = unary {
+            let value = self.expression()?;
+            return self.apply_unary(&op, value);
+        }
+
+        let left = self.term()?;
+        match self.peek() {
+            Some(op) if is_binary(op) => {
+                let op = self.next()?.text;
+                let right = self.expression()?;
+                Ok(apply_binary(&op, left, right))
+            }
+            _ => Ok(left),
+        }
+    }
+
+    fn term(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+        match token.text.as_str() {
+            "(" if !token.string => {
+                let value = self.expression()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            text => self
+                .known_value(text)
+                .ok_or_else(|| format!("undefined name '{}'", text)),
+        }
+    }
+
+    fn apply_unary(&self, op: &str, value: f64) -> Result<f64, String> {
+        Ok(match op {
+            "-" => -value,
+            "~" => !(value as i64) as f64,
+            "!" => (value == 0.0) as i64 as f64,
+            "sin" => value.sin(),
+            "cos" => value.cos(),
+            "tan" => value.tan(),
+            "exp" => value.exp(),
+            "log" => value.ln(),
+            "abs" => value.abs(),
+            "sqrt" => value.sqrt(),
+            "sign" => value.signum(),
+            "ceil" => value.ceil(),
+            "floor" => value.floor(),
+            // "@ address" reads a byte that's already been assembled.
+            _ => {
+                let address = value as usize;
+                if address >= ADDRESS_SPACE {
+                    return Err(format!("cannot read address {}", value));
+                }
+                self.memory[address] as f64
+            }
+        })
+    }
+
+    // A number, constant or label that is already defined.
+    fn known_value(&self, text: &str) -> Option<f64> {
+        number(text)
+            .or_else(|| self.constants.get(text).copied())
+            .or_else(|| self.labels.get(text).map(|&address| address as f64))
+    }
+
+    // A value that has to be known right away: a number, constant, defined label or "{ expression }".
+    fn value(&mut self) -> Result<f64, String> {
+        if self.peek() == Some("{") {
+            return self.calc();
+        }
+        let token = self.next()?;
+        self.known_value(&token.text)
+            .ok_or_else(|| format!("undefined name '{}'", token.text))
+    }
+
+    // A byte operand. Negative numbers down to -128 stand for their two's complement.
+    fn short(&mut self) -> Result<u8, String> {
+        let value = self.value()?;
+        self.check_byte(value, "value")
+    }
+
+    fn tiny(&mut self) -> Result<u8, String> {
+        let value = self.value()?;
+        Ok(self.check_range(value, 0.0, 15.0, "a nibble")? as u8)
+    }
+
+    fn check_byte(&self, value: f64, what: &str) -> Result<u8, String> {
+        let value = self
+            .check_range(value, -128.0, 255.0, "a byte")
+            .map_err(|_| format!("{} {} doesn't fit in a byte", what, value))?;
+        Ok(value as u8)
+    }
+
+    fn check_range(&self, value: f64, min: f64, max: f64, what: &str) -> Result<i64, String> {
+        let value = value.floor();
+        if value < min || value > max {
+            return Err(format!("{} doesn't fit in {}", value, what));
+        }
+        Ok(value as i64)
+    }
+
+    // An address for jumps, calls and I, which may be a label further down.
+    fn target(&mut self) -> Result<Target, String> {
+        if self.peek() == Some("{") {
+            let value = self.calc()?;
+            return Ok(Target::Known(self.check_range(
+                value,
+                0.0,
+                (ADDRESS_SPACE - 1) as f64,
+                "an address",
+            )? as usize));
+        }
+        let token = self.next()?;
+        self.target_of(&token.text)
+    }
+
+    fn target_of(&self, text: &str) -> Result<Target, String> {
+        if let Some(value) = self.known_value(text) {
+            return Ok(Target::Known(self.check_range(
+                value,
+                0.0,
+                (ADDRESS_SPACE - 1) as f64,
+                "an address",
+            )? as usize));
+        }
+        if self.is_register(text) || !is_name(text) {
+            return Err(format!("expected an address, got '{}'", text));
+        }
+        Ok(Target::Forward(text.to_string()))
+    }
+
+    // An instruction with a 12-bit address, e.g. 1NNN.
+    fn immediate(&mut self, op: u8, target: Target) -> Result<(), String> {
+        match target {
+            Target::Known(address) => {
+                if address > 0xFFF {
+                    return Err(format!("address {:#X} doesn't fit in 12 bits", address));
+                }
+                self.instruction(op | (address >> 8) as u8, address as u8)
+            }
+            Target::Forward(name) => {
+                self.fixups
+                    .push((name, Fixup::Instruction, self.here, self.line));
+                self.instruction(op, 0)
+            }
+        }
+    }
+
+    fn apply_fixup(&mut self, fixup: Fixup, at: usize, address: usize) -> Result<(), String> {
+        match fixup {
+            Fixup::Instruction => {
+                if address > 0xFFF {
+                    return Err(format!("address {:#X} doesn't fit in 12 bits", address));
+                }
+                self.memory[at] |= (address >> 8) as u8;
+                self.memory[at + 1] = address as u8;
+            }
+            Fixup::Long => {
+                self.memory[at] = (address >> 8) as u8;
+                self.memory[at + 1] = address as u8;
+            }
+            Fixup::UnpackHigh(nibble) => {
+                if address > 0xFFF {
+                    return Err(format!("address {:#X} doesn't fit in 12 bits", address));
+                }
+                self.memory[at + 1] = nibble << 4 | (address >> 8) as u8;
+            }
+            Fixup::UnpackLow => self.memory[at + 1] = address as u8,
+            Fixup::UnpackLongHigh => self.memory[at + 1] = (address >> 8) as u8,
+        }
+        Ok(())
+    }
+
+    // Points the "jump" at `at` to `address`.
+    fn patch_jump(&mut self, at: usize, address: usize) {
+        self.memory[at] = 0x10 | (address >> 8) as u8 & 0xF;
+        self.memory[at + 1] = address as u8;
+    }
+
+    fn instruction(&mut self, high: u8, low: u8) -> Result<(), String> {
+        self.byte(high)?;
+        self.byte(low)
+    }
+
+    fn byte(&mut self, value: u8) -> Result<(), String> {
+        if self.here < PROGRAM_START {
+            return Err(format!("cannot write below {:#X}", PROGRAM_START));
+        }
+        if self.here >= ADDRESS_SPACE {
+            return Err("the program doesn't fit in 64 KB".to_string());
+        }
+        if self.written[self.here] {
+            return Err(format!("address {:#X} is written twice", self.here));
+        }
+        self.memory[self.here] = value;
+        self.written[self.here] = true;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn define_label(&mut self, name: String, address: usize) -> Result<(), String> {
+        if self.labels.contains_key(&name) || self.constants.contains_key(&name) {
+            return Err(format!("'{}' is already defined", name));
+        }
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let token = self.next()?;
+        if token.string || !is_name(&token.text) || self.is_register(&token.text) {
+            return Err(format!("'{}' can't be used as a name", token.text));
+        }
+        Ok(token.text)
+    }
+
+    fn is_register(&self, text: &str) -> bool {
+        register_number(text).is_some() || self.aliases.contains_key(text)
+    }
+
+    fn register(&mut self) -> Result<u8, String> {
+        let token = self.next()?;
+        self.register_of(&token.text)
+    }
+
+    fn register_of(&self, text: &str) -> Result<u8, String> {
+        register_number(text)
+            .or_else(|| self.aliases.get(text).copied())
+            .ok_or_else(|| format!("expected a register, got '{}'", text))
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token.text != text || token.string {
+            return Err(format!("expected '{}', got '{}'", text, token.text));
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self
+            .tokens
+            .pop_front()
+            .ok_or_else(|| "unexpected end of the program".to_string())?;
+        self.line = token.line;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens
+            .front()
+            .filter(|token| !token.string)
+            .map(|token| token.text.as_str())
+    }
+}
+
+fn number_token(value: usize, line: usize) -> Token {
+    Token {
+        text: value.to_string(),
+        line,
+        string: false,
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    !text.is_empty()
+        && number(text).is_none()
+        && !text.starts_with(':')
+        && !matches!(text, "{" | "}" | "(" | ")")
+}
+
+fn is_unary(op: &str) -> bool {
+    matches!(
+        op,
+        "-" | "~"
+            | "!"
+            | "sin"
+            | "cos"
+            | "tan"
+            | "exp"
+            | "log"
+            | "abs"
+            | "sqrt"
+            | "sign"
+            | "ceil"
+            | "floor"
+            | "@"
+    )
+}
+
+fn is_binary(op: &str) -> bool {
+    matches!(
+        op,
+        "-" | "+"
+            | "*"
+            | "/"
+            | "%"
+            | "&"
+            | "|"
+            | "^"
+            | "<<"
+            | ">>"
+            | "pow"
+            | "min"
+            | "max"
+            | "<"
+            | "<="
+            | "=="
+            | "!="
+            | ">="
+            | ">"
+    )
+}
+
+fn apply_binary(op: &str, left: f64, right: f64) -> f64 {
+    let (a, b) = (left as i64, right as i64);
+    let truth = |condition: bool| condition as i64 as f64;
+    match op {
+        "-" => left - right,
+        "+" => left + right,
+        "*" => left * right,
+        "/" => left / right,
+        "%" => left % right,
+        "&" => (a & b) as f64,
+        "|" => (a | b) as f64,
+        "^" => (a ^ b) as f64,
+        "<<" => a.checked_shl(b as u32).unwrap_or(0) as f64,
+        ">>" => a.checked_shr(b as u32).unwrap_or(0) as f64,
+        "pow" => left.powf(right),
+        "min" => left.min(right),
+        "max" => left.max(right),
+        "<" => truth(left < right),
+        "<=" => truth(left <= right),
+        "==" => truth(left == right),
+        "!=" => truth(left != right),
+        ">=" => truth(left >= right),
+        _ => truth(left > right),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn main_first_starts_at_0x200() {
+        let rom = assemble(": main clear v0 := 5 loop again").unwrap();
+        assert_eq!(rom, [0x00, 0xE0, 0x60, 0x05, 0x12, 0x04]);
+    }
+
+    #[test]
+    fn jumps_to_a_later_main_and_calls_labels_by_name() {
+        let rom = assemble(": sub return\n: main sub jump main").unwrap();
+        assert_eq!(rom, [0x12, 0x04, 0x00, 0xEE, 0x22, 0x02, 0x12, 0x04]);
+    }
+
+    #[test]
+    fn if_then_and_if_begin_else_end() {
+        let source = ": main
+            if v0 == 3 then v1 := 1
+            if v0 key begin v2 := 1 else v2 := 2 end";
+        let rom = assemble(source).unwrap();
+        assert_eq!(
+            rom,
+            [0x40, 0x03, 0x61, 0x01, 0xE0, 0x9E, 0x12, 0x0C, 0x62, 0x01, 0x12, 0x0E, 0x62, 0x02]
+        );
+    }
+
+    #[test]
+    fn comparisons_go_through_vf() {
+        let rom = assemble(": main if v1 > 5 then v2 := 1").unwrap();
+        assert_eq!(rom, [0x6F, 0x05, 0x8F, 0x15, 0x3F, 0x01, 0x62, 0x01]);
+    }
+
+    #[test]
+    fn while_jumps_out_of_the_loop() {
+        let rom = assemble(": main loop while v0 != 10 v0 += 1 again").unwrap();
+        assert_eq!(rom, [0x40, 0x0A, 0x12, 0x08, 0x70, 0x01, 0x12, 0x00]);
+    }
+
+    #[test]
+    fn constants_macros_and_unpack() {
+        let source = "
+            :const SPEED 3
+            :calc TWICE { SPEED * 2 }
+            :macro add-to reg n { reg += n }
+            : main
+                add-to v0 TWICE
+                i := data
+                sprite v0 v1 2
+                :unpack 0xA data
+            : data 0xFF 0x81";
+        let rom = assemble(source).unwrap();
+        assert_eq!(
+            rom,
+            [0x70, 0x06, 0xA2, 0x0A, 0xD0, 0x12, 0x60, 0xA2, 0x61, 0x0A, 0xFF, 0x81]
+        );
+    }
+
+    #[test]
+    fn expressions_group_from_the_right() {
+        assert_eq!(assemble(": main :byte { 2 * 3 + 1 }").unwrap(), [8]);
+        assert_eq!(assemble(": main :byte { ( 2 * 3 ) + 1 }").unwrap(), [7]);
+    }
+
+    #[test]
+    fn string_modes_expand_every_character() {
+        let source = ":stringmode text \"AB\" { :byte { VALUE + 1 } } : main text \"BAB\"";
+        assert_eq!(assemble(source).unwrap(), [2, 1, 2]);
+    }
+
+    #[test]
+    fn reports_mistakes_with_the_line() {
+        assert_eq!(
+            assemble(": main\njump nowhere"),
+            Err("line 2: undefined name 'nowhere'".to_string())
+        );
+        assert_eq!(
+            assemble(": main\n\nv0 := 300"),
+            Err("line 3: value 300 doesn't fit in a byte".to_string())
+        );
+        assert!(assemble(": start clear").is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+
+use crate::chip8::CHIP8_FONTSET;
+
+// How long a message stays on screen.
+const TOAST_FRAMES: u32 = 120;
+
+const GLYPH_WIDTH: i32 = 4;
+const GLYPH_HEIGHT: i32 = 5;
+// Glyph pixels are this many times smaller than the window height.
+const TEXT_SCALE_DIVISOR: u32 = 120;
+const MARGIN: i32 = 2;
+
+const TEXT_COLOR: Color = Color::RGB(255, 255, 255);
+const BOX_COLOR: Color = Color::RGBA(0, 0, 0, 160);
+
+// Letters and symbols the CHIP-8 font doesn't have, in the same format:
+// five rows, the glyph in the upper four bits of every row.
+static EXTRA_GLYPHS: [(char, [u8; 5]); 36] = [
+    ('G', [0xF0, 0x80, 0xB0, 0x90, 0xF0]),
+    ('H', [0x90, 0x90, 0xF0, 0x90, 0x90]),
+    ('I', [0xE0, 0x40, 0x40, 0x40, 0xE0]),
+    ('J', [0x70, 0x20, 0x20, 0xA0, 0xE0]),
+    ('K', [0x90, 0xA0, 0xC0, 0xA0, 0x90]),
+    ('L', [0x80, 0x80, 0x80, 0x80, 0xF0]),
+    ('M', [0x90, 0xF0, 0xF0, 0x90, 0x90]),
+    ('N', [0x90, 0xD0, 0xB0, 0x90, 0x90]),
+    ('O', [0xF0, 0x90, 0x90, 0x90, 0xF0]),
+    ('P', [0xF0, 0x90, 0xF0, 0x80, 0x80]),
+    ('Q', [0xF0, 0x90, 0x90, 0xB0, 0xF0]),
+    ('R', [0xE0, 0x90, 0xE0, 0xA0, 0x90]),
+    ('S', [0xF0, 0x80, 0xF0, 0x10, 0xF0]),
+    ('T', [0xE0, 0x40, 0x40, 0x40, 0x40]),
+    ('U', [0x90, 0x90, 0x90, 0x90, 0xF0]),
+    ('V', [0x90, 0x90, 0x90, 0x90, 0x60]),
+    ('W', [0x90, 0x90, 0xF0, 0xF0, 0x90]),
+    ('X', [0x90, 0x90, 0x60, 0x90, 0x90]),
+    ('Y', [0xA0, 0xA0, 0x40, 0x40, 0x40]),
+    ('Z', [0xF0, 0x10, 0x60, 0x80, 0xF0]),
+    ('%', [0x90, 0x10, 0x60, 0x80, 0x90]),
+    (':', [0x00, 0x40, 0x00, 0x40, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x40]),
+    ('/', [0x10, 0x10, 0x60, 0x80, 0x80]),
+    ('-', [0x00, 0x00, 0xF0, 0x00, 0x00]),
+    ('!', [0x40, 0x40, 0x40, 0x00, 0x40]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0xF0]),
+    ('(', [0x20, 0x40, 0x40, 0x40, 0x20]),
+    (')', [0x40, 0x20, 0x20, 0x20, 0x40]),
+    ('[', [0x60, 0x40, 0x40, 0x40, 0x60]),
+    (']', [0x60, 0x20, 0x20, 0x20, 0x60]),
+    ('+', [0x00, 0x40, 0xE0, 0x40, 0x00]),
+    (',', [0x00, 0x00, 0x00, 0x40, 0x80]),
+    ('\'', [0x40, 0x40, 0x00, 0x00, 0x00]),
+    ('>', [0x80, 0x40, 0x20, 0x40, 0x80]),
+    ('*', [0x00, 0xA0, 0x40, 0xA0, 0x00]),
+];
+
+static UNKNOWN_GLYPH: [u8; 5] = [0xE0, 0x10, 0x60, 0x00, 0x40];
+
+// A list to pick from, drawn over the whole window.
+pub struct Menu {
+    pub title: String,
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+// Text drawn on top of the picture in the window. It never goes anywhere near the emulated framebuffer,
+// so screenshots, recordings and the game itself don't see it.
+pub struct Osd {
+    toast: Option<(String, u32)>,
+    pub show_stats: bool,
+    // Frames and instructions per second, measured by the main loop.
+    pub stats: (u32, u32),
+    pub paused: bool,
+    pub menu: Option<Menu>,
+}
+
+impl Osd {
+    pub fn new(show_stats: bool) -> Self {
+        Osd {
+            toast: None,
+            show_stats,
+            stats: (0, 0),
+            paused: false,
+            menu: None,
+        }
+    }
+
+    pub fn show_message(&mut self, message: &str) {
+        self.toast = Some((message.to_string(), TOAST_FRAMES));
+    }
+
+    // Called once per presented frame, after the picture and before `canvas.present()`.
+    pub fn render(&mut self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let (_, output_height) = canvas.output_size()?;
+        let scale = (output_height / TEXT_SCALE_DIVISOR).max(1) as i32;
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        if self.show_stats {
+            let text = format!("FPS {} IPS {}", self.stats.0, self.stats.1);
+            draw_text(canvas, &text, MARGIN * scale, MARGIN * scale, scale)?;
+        }
+
+        if self.paused {
+            let (output_width, _) = canvas.output_size()?;
+            let x = output_width as i32 - (text_width("PAUSED") + MARGIN) * scale;
+            draw_text(canvas, "PAUSED", x, MARGIN * scale, scale)?;
+        }
+
+        if let Some(menu) = &self.menu {
+            let line_height = (GLYPH_HEIGHT + MARGIN * 2) * scale;
+            let x = MARGIN * 2 * scale;
+            let mut y = line_height;
+            draw_text(canvas, &menu.title, x, y, scale)?;
+            y += line_height;
+            for (i, item) in menu.items.iter().enumerate() {
+                y += line_height;
+                let marker = if i == menu.selected { "> " } else { "  " };
+                draw_text(canvas, &format!("{}{}", marker, item), x, y, scale)?;
+            }
+        }
+
+        if let Some((message, frames_left)) = &mut self.toast {
+            let y = output_height as i32 - (GLYPH_HEIGHT + MARGIN * 2) * scale;
+            draw_text(canvas, message, MARGIN * scale, y, scale)?;
+
+            *frames_left -= 1;
+            if *frames_left == 0 {
+                self.toast = None;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn text_width(text: &str) -> i32 {
+    text.chars().count() as i32 * (GLYPH_WIDTH + 1) - 1
+}
+
+fn draw_text(
+    canvas: &mut Canvas<Window>,
+    text: &str,
+    x: i32,
+    y: i32,
+    scale: i32,
+) -> Result<(), String> {
+    // A dark box behind the text keeps it readable on any palette.
+    canvas.set_draw_color(BOX_COLOR);
+    canvas.fill_rect(Rect::new(
+        x - scale,
+        y - scale,
+        ((text_width(text) + 2) * scale) as u32,
+        ((GLYPH_HEIGHT + 2) * scale) as u32,
+    ))?;
+
+    canvas.set_draw_color(TEXT_COLOR);
+    let mut rects = Vec::new();
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i as i32 * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (0x80 >> col) != 0 {
+                    rects.push(Rect::new(
+                        left + col * scale,
+                        y + row as i32 * scale,
+                        scale as u32,
+                        scale as u32,
+                    ));
+                }
+            }
+        }
+    }
+    canvas.fill_rects(&rects)
+}
+
+// Digits and A-F come straight from the CHIP-8 font.
+fn glyph(c: char) -> [u8; 5] {
+    let c = c.to_ascii_uppercase();
+    if c == ' ' {
+        return [0; 5];
+    }
+    if let Some(digit) = c.to_digit(16) {
+        let start = digit as usize * 5;
+        let mut rows = [0; 5];
+        rows.copy_from_slice(&CHIP8_FONTSET[start..start + 5]);
+        return rows;
+    }
+    EXTRA_GLYPHS
+        .iter()
+        .find(|(glyph, _)| *glyph == c)
+        .map(|(_, rows)| *rows)
+        .unwrap_or(UNKNOWN_GLYPH)
+}
@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::Path;
+
+use super::config_file::for_each_line;
+
+// Colour 0 is the background, 1 the foreground. 2 and 3 are for framebuffers with more than one plane.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [(u8, u8, u8); 4],
+}
+
+// The CHIP-8X colour board's colours, in the order of `ColorLayer`'s colour numbers:
+// black, red, blue, violet, green, yellow, aqua and white.
+pub const CHIP8X_COLORS: [(u8, u8, u8); 8] = [
+    (0x00, 0x00, 0x00),
+    (0xFF, 0x00, 0x00),
+    (0x00, 0x00, 0xFF),
+    (0xFF, 0x00, 0xFF),
+    (0x00, 0xFF, 0x00),
+    (0xFF, 0xFF, 0x00),
+    (0x00, 0xFF, 0xFF),
+    (0xFF, 0xFF, 0xFF),
+];
+
+fn builtin(name: &str, colors: [u32; 4]) -> Palette {
+    let rgb = |c: u32| ((c >> 16) as u8, (c >> 8) as u8, c as u8);
+    Palette {
+        name: name.to_string(),
+        colors: [
+            rgb(colors[0]),
+            rgb(colors[1]),
+            rgb(colors[2]),
+            rgb(colors[3]),
+        ],
+    }
+}
+
+pub fn builtins() -> Vec<Palette> {
+    vec![
+        builtin("green", [0x000000, 0x00FA00, 0x00A000, 0x005000]),
+        builtin("amber", [0x1A0F00, 0xFFB000, 0xB07800, 0x604000]),
+        builtin("lcd", [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F]),
+        builtin("high-contrast", [0x000000, 0xFFFFFF, 0xFFFF00, 0x00FFFF]),
+        // Okabe-Ito colours, which stay distinguishable with every common kind of colour blindness.
+        builtin("colorblind", [0x000000, 0x56B4E9, 0xE69F00, 0xF0E442]),
+    ]
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        builtins().remove(0)
+    }
+}
+
+impl Palette {
+    // A built-in palette name or a path to a palette file.
+    pub fn find(name: &str) -> Result<Palette, String> {
+        if let Some(palette) = builtins().into_iter().find(|p| p.name == name) {
+            return Ok(palette);
+        }
+        if Path::new(name).exists() {
+            return Palette::load(name);
+        }
+        Err(format!("unknown palette: {}", name))
+    }
+
+    // The palette remembered for a ROM ("game.ch8" -> "game.ch8.palette"), if there is one.
+    pub fn for_rom(rom_path: &str) -> Result<Option<Palette>, String> {
+        let path = rom_palette_path(rom_path);
+        if Path::new(&path).exists() {
+            Palette::load(&path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    // One "key = value" per line, colours as hex RGB:
+    //
+    //     name = amber
+    //     background = #1A0F00
+    //     foreground = #FFB000
+    //     color2 = #B07800
+    //     color3 = #604000
+    //
+    // color2 and color3 are optional and default to the foreground.
+    pub fn load(path: &str) -> Result<Palette, String> {
+        let mut name = None;
+        let mut colors: [Option<(u8, u8, u8)>; 4] = [None; 4];
+
+        for_each_line(path, |line| {
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = parts.next().map(str::trim);
+            let invalid = || format!("invalid line '{}'", line);
+
+            let index = match key {
+                "name" => {
+                    name = Some(value.ok_or_else(invalid)?.to_string());
+                    return Ok(());
+                }
+                "background" => 0,
+                "foreground" => 1,
+                "color2" => 2,
+                "color3" => 3,
+                _ => return Err(invalid()),
+            };
+            colors[index] = Some(value.and_then(parse_color).ok_or_else(invalid)?);
+            Ok(())
+        })?;
+
+        let background = colors[0].ok_or_else(|| format!("{}: background is missing", path))?;
+        let foreground = colors[1].ok_or_else(|| format!("{}: foreground is missing", path))?;
+
+        Ok(Palette {
+            name: name.unwrap_or_else(|| path.to_string()),
+            colors: [
+                background,
+                foreground,
+                colors[2].unwrap_or(foreground),
+                colors[3].unwrap_or(foreground),
+            ],
+        })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let hex = |(r, g, b): (u8, u8, u8)| format!("#{:02X}{:02X}{:02X}", r, g, b);
+        let content = format!(
+            "name = {}\nbackground = {}\nforeground = {}\ncolor2 = {}\ncolor3 = {}\n",
+            self.name,
+            hex(self.colors[0]),
+            hex(self.colors[1]),
+            hex(self.colors[2]),
+            hex(self.colors[3]),
+        );
+        fs::write(path, content).map_err(|e| format!("cannot write {}: {}", path, e))
+    }
+}
+
+pub fn rom_palette_path(rom_path: &str) -> String {
+    format!("{}.palette", rom_path)
+}
+
+// Cycles through the built-in palettes plus the one the emulator started with, if that's a custom one.
+pub struct PaletteSet {
+    palettes: Vec<Palette>,
+    current: usize,
+}
+
+impl PaletteSet {
+    pub fn new(initial: Palette) -> Self {
+        let mut palettes = builtins();
+        let current = match palettes.iter().position(|p| *p == initial) {
+            Some(i) => i,
+            None => {
+                palettes.insert(0, initial);
+                0
+            }
+        };
+        PaletteSet { palettes, current }
+    }
+
+    pub fn current(&self) -> &Palette {
+        &self.palettes[self.current]
+    }
+
+    pub fn next(&mut self) -> &Palette {
+        self.current = (self.current + 1) % self.palettes.len();
+        self.current()
+    }
+}
+
+pub fn parse_color(value: &str) -> Option<(u8, u8, u8)> {
+    let hex = value.trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    Some(((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+}
@@ -0,0 +1,346 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_END: &[u8] = b"EOF";
+// A record at this offset would read as the end marker.
+const IPS_END_OFFSET: usize = 0x454F46;
+const IPS_MAX_OFFSET: usize = 0xFFFFFF;
+const IPS_MAX_RECORD: usize = 0xFFFF;
+
+const BPS_MAGIC: &[u8] = b"BPS1";
+// Source, target and patch CRC32s.
+const BPS_FOOTER: usize = 12;
+
+// A patch with the same name as the ROM ("game.ch8" -> "game.ips" or "game.bps"), if there is one.
+pub fn patch_for_rom(rom_path: &str) -> Option<PathBuf> {
+    ["ips", "bps"]
+        .iter()
+        .map(|ext| Path::new(rom_path).with_extension(ext))
+        .find(|path| path.exists())
+}
+
+pub fn apply_file(rom: &[u8], patch_path: &Path) -> Result<Vec<u8>, String> {
+    let patch =
+        fs::read(patch_path).map_err(|e| format!("cannot read {}: {}", patch_path.display(), e))?;
+    apply(rom, &patch).map_err(|e| format!("cannot apply {}: {}", patch_path.display(), e))
+}
+
+// Tells IPS and BPS apart by their header.
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err("not an IPS or BPS patch".to_string())
+    }
+}
+
+// Records of a 24-bit offset, a 16-bit size and that many bytes. A size of 0 is a run of one byte
+// instead. An optional 24-bit length after the end marker truncates the result.
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = Reader::new(&patch[IPS_MAGIC.len()..]);
+    let mut target = rom.to_vec();
+
+    loop {
+        if reader.remaining().starts_with(IPS_END) {
+            reader.take(IPS_END.len())?;
+            if reader.remaining().len() >= 3 {
+                target.truncate(reader.be(3)?);
+            }
+            return Ok(target);
+        }
+
+        let offset = reader.be(3)?;
+        let (size, data) = match reader.be(2)? {
+            0 => {
+                let size = reader.be(2)?;
+                (size, vec![reader.byte()?; size])
+            }
+            size => (size, reader.take(size)?.to_vec()),
+        };
+
+        if target.len() < offset + size {
+            target.resize(offset + size, 0);
+        }
+        target[offset..offset + size].copy_from_slice(&data);
+    }
+}
+
+// The beat patch format: sizes, then actions copying from the source, the patch or the output so far,
+// and CRC32s of the source, the target and the patch itself.
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.len() < BPS_MAGIC.len() + BPS_FOOTER {
+        return Err("patch is cut short".to_string());
+    }
+    let footer = &patch[patch.len() - BPS_FOOTER..];
+    let crc =
+        |i: usize| u32::from_le_bytes([footer[i], footer[i + 1], footer[i + 2], footer[i + 3]]);
+
+    if crc32fast::hash(&patch[..patch.len() - 4]) != crc(8) {
+        return Err("the patch is corrupt".to_string());
+    }
+    if crc32fast::hash(rom) != crc(0) {
+        return Err("the patch is for a different ROM".to_string());
+    }
+
+    let mut reader = Reader::new(&patch[BPS_MAGIC.len()..patch.len() - BPS_FOOTER]);
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.take(metadata_size)?;
+    if source_size != rom.len() {
+        return Err("the patch is for a different ROM".to_string());
+    }
+
+    let mut target = Vec::new();
+    let mut source_offset: isize = 0;
+    let mut target_offset: isize = 0;
+    while !reader.remaining().is_empty() {
+        let command = reader.number()?;
+        let length = (command >> 2) + 1;
+        // No action may write past the size the patch promises, target.len() never exceeds it.
+        if length > target_size - target.len() {
+            return Err("the patch writes past the size of the patched ROM".to_string());
+        }
+        match command & 3 {
+            // SourceRead: the source bytes at the same position.
+            0 => {
+                let bytes = range(rom, target.len(), length)?;
+                target.extend_from_slice(bytes);
+            }
+            // TargetRead: bytes from the patch.
+            1 => target.extend_from_slice(reader.take(length)?),
+            // SourceCopy: bytes from anywhere in the source.
+            2 => {
+                source_offset += reader.signed_number()?;
+                let start = usize::try_from(source_offset).map_err(|_| out_of_range())?;
+                target.extend_from_slice(range(rom, start, length)?);
+                source_offset += length as isize;
+            }
+            // TargetCopy: bytes from the output so far, which may overlap what's being written.
+            _ => {
+                target_offset += reader.signed_number()?;
+                for _ in 0..length {
+                    let index = usize::try_from(target_offset).map_err(|_| out_of_range())?;
+                    let byte = *target.get(index).ok_or_else(out_of_range)?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size || crc32fast::hash(&target) != crc(4) {
+        return Err("the patched ROM doesn't match the patch's checksum".to_string());
+    }
+    Ok(target)
+}
+
+// Writes an IPS or BPS patch, depending on the extension, that turns `source` into `target`.
+pub fn create(source: &[u8], target: &[u8], patch_path: &Path) -> Result<(), String> {
+    let patch = match patch_path.extension().and_then(|ext| ext.to_str()) {
+        Some("bps") => create_bps(source, target),
+        Some("ips") => create_ips(source, target)?,
+        _ => {
+            return Err(format!(
+                "{}: patches end in .ips or .bps",
+                patch_path.display()
+            ))
+        }
+    };
+    fs::write(patch_path, patch)
+        .map_err(|e| format!("cannot write {}: {}", patch_path.display(), e))
+}
+
+fn create_ips(source: &[u8], target: &[u8]) -> Result<Vec<u8>, String> {
+    if target.len() > IPS_MAX_OFFSET {
+        return Err("IPS patches only reach 16 MB".to_string());
+    }
+
+    let mut patch = IPS_MAGIC.to_vec();
+    let mut offset = 0;
+    while offset < target.len() {
+        if source.get(offset) == Some(&target[offset]) {
+            offset += 1;
+            continue;
+        }
+
+        // Back up one byte rather than writing a record that looks like the end marker.
+        let start = if offset == IPS_END_OFFSET {
+            offset - 1
+        } else {
+            offset
+        };
+        let mut end = offset;
+        while end < target.len()
+            && end - start < IPS_MAX_RECORD
+            && source.get(end) != Some(&target[end])
+        {
+            end += 1;
+        }
+
+        patch.extend_from_slice(&(start as u32).to_be_bytes()[1..]);
+        patch.extend_from_slice(&((end - start) as u16).to_be_bytes());
+        patch.extend_from_slice(&target[start..end]);
+        offset = end;
+    }
+    patch.extend_from_slice(IPS_END);
+    if target.len() < source.len() {
+        patch.extend_from_slice(&(target.len() as u32).to_be_bytes()[1..]);
+    }
+    Ok(patch)
+}
+
+// Only uses SourceRead and TargetRead, which is plenty for ROMs of a few kilobytes.
+fn create_bps(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = BPS_MAGIC.to_vec();
+    push_number(&mut patch, source.len());
+    push_number(&mut patch, target.len());
+    push_number(&mut patch, 0);
+
+    let mut offset = 0;
+    while offset < target.len() {
+        let same = |i: usize| source.get(i) == Some(&target[i]);
+        let matching = same(offset);
+        let mut end = offset;
+        while end < target.len() && same(end) == matching {
+            end += 1;
+        }
+
+        let command = (end - offset - 1) << 2;
+        if matching {
+            push_number(&mut patch, command);
+        } else {
+            push_number(&mut patch, command | 1);
+            patch.extend_from_slice(&target[offset..end]);
+        }
+        offset = end;
+    }
+
+    patch.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+    patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+    let patch_crc = crc32fast::hash(&patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+    patch
+}
+
+// BPS numbers: 7 bits per byte, the last byte has the top bit set.
+fn push_number(patch: &mut Vec<u8>, mut number: usize) {
+    loop {
+        let bits = (number & 0x7F) as u8;
+        number >>= 7;
+        if number == 0 {
+            patch.push(0x80 | bits);
+            return;
+        }
+        patch.push(bits);
+        number -= 1;
+    }
+}
+
+fn range(data: &[u8], start: usize, length: usize) -> Result<&[u8], String> {
+    let end = start.checked_add(length).ok_or_else(out_of_range)?;
+    data.get(start..end).ok_or_else(out_of_range)
+}
+
+fn out_of_range() -> String {
+    "the patch reads outside the ROM".to_string()
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < count {
+            return Err("patch is cut short".to_string());
+        }
+        let (taken, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    // A big-endian number of `size` bytes.
+    fn be(&mut self, size: usize) -> Result<usize, String> {
+        Ok(self
+            .take(size)?
+            .iter()
+            .fold(0, |n, &byte| n << 8 | byte as usize))
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        let mut number = 0;
+        let mut shift = 1;
+        loop {
+            let byte = self.byte()?;
+            number += (byte & 0x7F) as usize * shift;
+            if byte & 0x80 != 0 {
+                return Ok(number);
+            }
+            shift = shift
+                .checked_shl(7)
+                .filter(|&shift| shift < usize::MAX >> 8)
+                .ok_or_else(|| "invalid number in patch".to_string())?;
+            number += shift;
+        }
+    }
+
+    // The lowest bit is the sign.
+    fn signed_number(&mut self) -> Result<isize, String> {
+        let number = self.number()?;
+        let value = (number >> 1) as isize;
+        Ok(if number & 1 != 0 { -value } else { value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_ips_and_bps() {
+        let source = b"\x00\xE0\xA2\x2A\x60\x0C\x61\x08\xD0\x1F".to_vec();
+        let target = b"\x00\xE0\xA2\x2A\x60\x10\x61\x08\xD0\x1F\x12\x00".to_vec();
+        assert_eq!(
+            apply(&source, &create_ips(&source, &target).unwrap()),
+            Ok(target.clone())
+        );
+        assert_eq!(apply(&source, &create_bps(&source, &target)), Ok(target));
+    }
+
+    #[test]
+    fn bps_cannot_write_past_the_target_size() {
+        let source = [0xAA];
+        // Promises one byte, then reads two from the patch.
+        let mut patch = BPS_MAGIC.to_vec();
+        for number in &[1, 1, 0, (1 << 2) | 1] {
+            push_number(&mut patch, *number);
+        }
+        patch.extend_from_slice(&[0xBB, 0xCC]);
+        patch.extend_from_slice(&crc32fast::hash(&source).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(&[0xBB]).to_le_bytes());
+        let patch_crc = crc32fast::hash(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+
+        assert_eq!(
+            apply(&source, &patch),
+            Err("the patch writes past the size of the patched ROM".to_string())
+        );
+    }
+}
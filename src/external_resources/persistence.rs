@@ -0,0 +1,109 @@
+// XOR drawing makes sprites blink every time a game moves them.
+// Real CRT phosphor kept glowing for a moment, which hid most of that. This filter fakes it.
+
+// Exponential fades are down to this fraction of full brightness after the configured number of frames.
+const EXPONENTIAL_FLOOR: f32 = 1.0 / 32.0;
+const BLACK_LEVEL: f32 = 1.0 / 255.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DecayCurve {
+    Linear,
+    Exponential,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Persistence {
+    Off,
+    // Pixels that turn off fade out over the given number of frames.
+    Fade(u32, DecayCurve),
+    // A pixel is lit if it was lit in this frame or the previous one.
+    Blend,
+}
+
+impl Persistence {
+    // "off", "blend", "linear:<frames>" or "exponential:<frames>".
+    pub fn from_name(name: &str) -> Option<Persistence> {
+        let mut parts = name.splitn(2, ':');
+        let mode = parts.next()?;
+        let frames = parts.next().map(|f| f.parse().ok().filter(|&f| f > 0));
+
+        match (mode, frames) {
+            ("off", None) => Some(Persistence::Off),
+            ("blend", None) => Some(Persistence::Blend),
+            ("linear", Some(Some(frames))) => Some(Persistence::Fade(frames, DecayCurve::Linear)),
+            ("exponential", Some(Some(frames))) => {
+                Some(Persistence::Fade(frames, DecayCurve::Exponential))
+            }
+            _ => None,
+        }
+    }
+
+    // The mode after this one when cycling at runtime. Fades keep their length.
+    pub fn next(self, fade_frames: u32) -> Persistence {
+        match self {
+            Persistence::Off => Persistence::Fade(fade_frames, DecayCurve::Linear),
+            Persistence::Fade(frames, DecayCurve::Linear) => {
+                Persistence::Fade(frames, DecayCurve::Exponential)
+            }
+            Persistence::Fade(_, DecayCurve::Exponential) => Persistence::Blend,
+            Persistence::Blend => Persistence::Off,
+        }
+    }
+}
+
+// Turns a framebuffer of any size into per-pixel brightness between 0 and 1.
+pub struct PersistenceFilter {
+    pub mode: Persistence,
+    levels: Vec<f32>,
+    previous: Vec<u8>,
+}
+
+impl PersistenceFilter {
+    pub fn new(mode: Persistence) -> Self {
+        PersistenceFilter {
+            mode,
+            levels: Vec::new(),
+            previous: Vec::new(),
+        }
+    }
+
+    // Advances the filter by one frame. Must be called exactly once per 60 Hz frame,
+    // even if the framebuffer didn't change, otherwise fades stall.
+    pub fn apply(&mut self, pixels: &[u8]) -> &[f32] {
+        if self.levels.len() != pixels.len() {
+            // Resolution changed, history for the old size is meaningless.
+            self.levels = vec![0.0; pixels.len()];
+            self.previous = vec![0; pixels.len()];
+        }
+
+        for ((level, previous), &pixel) in self
+            .levels
+            .iter_mut()
+            .zip(self.previous.iter_mut())
+            .zip(pixels.iter())
+        {
+            let on = pixel != 0;
+            *level = match self.mode {
+                Persistence::Off => on as u8 as f32,
+                Persistence::Blend => (on || *previous != 0) as u8 as f32,
+                Persistence::Fade(_, _) if on => 1.0,
+                Persistence::Fade(frames, curve) => decay(*level, frames, curve),
+            };
+            *previous = pixel;
+        }
+
+        &self.levels
+    }
+}
+
+fn decay(level: f32, frames: u32, curve: DecayCurve) -> f32 {
+    let level = match curve {
+        DecayCurve::Linear => level - 1.0 / frames as f32,
+        DecayCurve::Exponential => level * EXPONENTIAL_FLOOR.powf(1.0 / frames as f32),
+    };
+    if level < BLACK_LEVEL {
+        0.0
+    } else {
+        level
+    }
+}
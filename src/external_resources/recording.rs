@@ -0,0 +1,311 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use super::filters::{self, Filter};
+use super::image::Image;
+use super::palette::Palette;
+use crate::chip8::ColorLayer;
+
+pub const RECORDING_DIR: &str = "recordings";
+
+const FRAMES_PER_SECOND: u32 = 60;
+// Frames are kept in memory until the clip is saved. A game that changes the screen every frame
+// fills this in a few minutes with a scaling filter on, far longer without.
+const MAX_CLIP_BYTES: usize = 256 * 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecordingFormat {
+    Gif,
+    Apng,
+}
+
+impl RecordingFormat {
+    pub fn from_name(name: &str) -> Option<RecordingFormat> {
+        match name {
+            "gif" => Some(RecordingFormat::Gif),
+            "apng" | "png" => Some(RecordingFormat::Apng),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<RecordingFormat> {
+        RecordingFormat::from_name(&path.extension()?.to_string_lossy().to_lowercase())
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "gif",
+            RecordingFormat::Apng => "png",
+        }
+    }
+}
+
+// Collects one image per emulated 60 Hz frame, however long those frames took on the host.
+// Identical frames in a row are stored once with a longer duration, which keeps files small:
+// most games only change a few times per second.
+pub struct Recording {
+    frames: Vec<(Image, u32)>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Recording {
+            frames: Vec::new(),
+            bytes: 0,
+            max_bytes: MAX_CLIP_BYTES,
+        }
+    }
+}
+
+impl Recording {
+    // Every frame of a clip has the same size and the clip has a memory limit, so a frame of
+    // another size or one that doesn't fit anymore is refused with the reason.
+    // The caller saves what's there and carries on in a new recording.
+    pub fn push(&mut self, image: &Image) -> Result<(), String> {
+        if let Some((first, _)) = self.frames.first() {
+            if (first.width, first.height) != (image.width, image.height) {
+                return Err(format!(
+                    "the screen changed from {}x{} to {}x{}",
+                    first.width, first.height, image.width, image.height
+                ));
+            }
+        }
+        if let Some((last, duration)) = self.frames.last_mut() {
+            if last == image {
+                *duration += 1;
+                return Ok(());
+            }
+        }
+
+        let size = image.width * image.height * 3;
+        if !self.frames.is_empty() && self.bytes + size > self.max_bytes {
+            return Err(format!(
+                "the clip reached the {} MB limit",
+                self.max_bytes / (1024 * 1024)
+            ));
+        }
+        self.bytes += size;
+        self.frames.push((image.clone(), 1));
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path, format: RecordingFormat) -> Result<(), String> {
+        if self.frames.is_empty() {
+            return Err("nothing was recorded".to_string());
+        }
+
+        let file =
+            File::create(path).map_err(|e| format!("cannot create {}: {}", path.display(), e))?;
+        let writer = BufWriter::new(file);
+
+        match format {
+            RecordingFormat::Gif => self.save_gif(writer),
+            RecordingFormat::Apng => self.save_apng(writer),
+        }
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))
+    }
+
+    fn save_gif(&self, writer: BufWriter<File>) -> Result<(), String> {
+        let (width, height) = (
+            self.frames[0].0.width as u16,
+            self.frames[0].0.height as u16,
+        );
+        let mut encoder =
+            gif::Encoder::new(writer, width, height, &[]).map_err(|e| e.to_string())?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(|e| e.to_string())?;
+
+        // GIF delays are in 1/100 s, which can't express 1/60 s exactly.
+        // Rounding the running total instead of every frame keeps the clip in sync over time.
+        let mut elapsed = 0;
+        for (image, duration) in &self.frames {
+            let start = elapsed * 100 / FRAMES_PER_SECOND;
+            elapsed += duration;
+            let end = elapsed * 100 / FRAMES_PER_SECOND;
+
+            let mut frame = gif::Frame::from_rgb_speed(width, height, &image.to_rgb_bytes(), 10);
+            frame.delay = (end - start).min(u16::MAX as u32) as u16;
+            encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn save_apng(&self, writer: BufWriter<File>) -> Result<(), String> {
+        let (width, height) = (
+            self.frames[0].0.width as u32,
+            self.frames[0].0.height as u32,
+        );
+        let mut encoder = png::Encoder::new(writer, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(self.frames.len() as u32, 0)
+            .map_err(|e| e.to_string())?;
+
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        for (image, duration) in &self.frames {
+            // APNG delays are fractions, so 60 fps is exact.
+            writer
+                .set_frame_delay(
+                    (*duration).min(u16::MAX as u32) as u16,
+                    FRAMES_PER_SECOND as u16,
+                )
+                .map_err(|e| e.to_string())?;
+            writer
+                .write_image_data(&image.to_rgb_bytes())
+                .map_err(|e| e.to_string())?;
+        }
+        writer.finish().map_err(|e| e.to_string())
+    }
+}
+
+// Records straight from the framebuffer, for runs without a window.
+// The first clip goes to `path`. A clip that can't go on (see `Recording::push`) is saved right away
+// and the recording continues in "out-2.gif", "out-3.gif" and so on.
+pub struct FramebufferRecording {
+    clip: Recording,
+    clips: usize,
+    path: PathBuf,
+    format: RecordingFormat,
+    palette: Palette,
+    filters: Vec<Filter>,
+}
+
+impl FramebufferRecording {
+    pub fn new(
+        path: &Path,
+        format: RecordingFormat,
+        palette: Palette,
+        filters: Vec<Filter>,
+    ) -> Self {
+        FramebufferRecording {
+            clip: Recording::default(),
+            clips: 1,
+            path: path.to_path_buf(),
+            format,
+            palette,
+            filters,
+        }
+    }
+
+    pub fn push(&mut self, pixels: &[[u8; 64]], colors: Option<&ColorLayer>) -> Result<(), String> {
+        let image = Image::from_framebuffer(pixels.as_flattened(), 64, None, &self.palette, colors);
+        let image = filters::apply_chain(&self.filters, image);
+        if let Err(reason) = self.clip.push(&image) {
+            let path = self.clip_path();
+            std::mem::take(&mut self.clip).save(&path, self.format)?;
+            self.clips += 1;
+            eprintln!(
+                "{}, saved {} and continuing in {}",
+                reason,
+                path.display(),
+                self.clip_path().display()
+            );
+            // An empty clip takes any frame.
+            self.clip.push(&image)?;
+        }
+        Ok(())
+    }
+
+    // Saves the clip in progress. Returns how many files the recording took.
+    pub fn finish(self) -> Result<usize, String> {
+        self.clip.save(&self.clip_path(), self.format)?;
+        Ok(self.clips)
+    }
+
+    fn clip_path(&self) -> PathBuf {
+        if self.clips == 1 {
+            self.path.clone()
+        } else {
+            numbered(&self.path, self.clips)
+        }
+    }
+}
+
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, n, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(value: u8) -> Image {
+        Image {
+            width: 2,
+            height: 1,
+            pixels: vec![(value, value, value); 2],
+        }
+    }
+
+    #[test]
+    fn repeated_frames_are_merged() {
+        let mut recording = Recording::default();
+        for value in &[0, 0, 0, 1] {
+            recording.push(&image(*value)).unwrap();
+        }
+
+        assert_eq!(recording.frames.len(), 2);
+        assert_eq!(recording.frames[0].1, 3);
+    }
+
+    #[test]
+    fn a_full_clip_refuses_new_frames_but_not_repeats() {
+        let mut recording = Recording {
+            max_bytes: 12,
+            ..Recording::default()
+        };
+        recording.push(&image(0)).unwrap();
+        recording.push(&image(1)).unwrap();
+
+        assert!(recording.push(&image(2)).is_err());
+        assert!(recording.push(&image(1)).is_ok());
+        assert_eq!(recording.frames.len(), 2);
+    }
+
+    #[test]
+    fn a_change_of_screen_size_is_refused() {
+        let mut recording = Recording::default();
+        recording.push(&image(0)).unwrap();
+
+        let tall = Image {
+            width: 2,
+            height: 2,
+            pixels: vec![(0, 0, 0); 4],
+        };
+        assert_eq!(
+            recording.push(&tall),
+            Err("the screen changed from 2x1 to 2x2".to_string())
+        );
+    }
+
+    #[test]
+    fn headless_recordings_continue_in_numbered_files() {
+        let dir = std::env::temp_dir().join(format!("chip8-recording-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("run.gif");
+
+        let mut video =
+            FramebufferRecording::new(&path, RecordingFormat::Gif, Palette::default(), Vec::new());
+        video.push(&[[0; 64]; 32], None).unwrap();
+        video.push(&[[1; 64]; 64], None).unwrap();
+        let clips = video.finish().unwrap();
+
+        assert_eq!(clips, 2);
+        assert!(path.exists());
+        assert!(dir.join("run-2.gif").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
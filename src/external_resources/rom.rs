@@ -1,14 +1,92 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
-use std::io::Error;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
 
-const SIZE_LIMIT: usize = 0xFFF - 0x200;
+use super::octo::{self, Cartridge, OctoOptions};
+use super::octo_assembler;
+use crate::chip8::MEMORY_SIZE;
 
-pub fn read_rom(file: &str) -> Result<[u8; SIZE_LIMIT], Error> {
-    let mut f = File::open(file)?;
-    let mut rom_file: [u8; SIZE_LIMIT] = [0; SIZE_LIMIT];
+pub struct Rom {
+    pub bytes: Vec<u8>,
+    // The settings saved with an Octo cartridge.
+    pub octo: Option<OctoOptions>,
+}
+
+// The ROM exactly as it is on disk, so it can be hashed. Anything bigger than the whole memory is an error,
+// whether it fits the memory layout is checked once the layout is known.
+// Octo cartridges are assembled from the source they carry.
+pub fn read_rom(file: &str) -> Result<Rom, String> {
+    let mut rom_file = Vec::new();
+    File::open(file)
+        .and_then(|mut f| f.read_to_end(&mut rom_file))
+        .map_err(|e| format!("cannot read file: {}", e))?;
+
+    let mut octo = None;
+    if octo::is_cartridge(&rom_file) {
+        let cartridge = Cartridge::decode(&rom_file).map_err(|e| format!("{}: {}", file, e))?;
+        rom_file = octo_assembler::assemble(&cartridge.program)
+            .map_err(|e| format!("{}: cannot assemble the cartridge: {}", file, e))?;
+        octo = Some(cartridge.options);
+    }
+    if rom_file.len() > MEMORY_SIZE {
+        return Err(format!(
+            "{} is {} bytes, at most {} fit in memory",
+            file,
+            rom_file.len(),
+            MEMORY_SIZE
+        ));
+    }
+    Ok(Rom {
+        bytes: rom_file,
+        octo,
+    })
+}
+
+pub fn is_chip8x(file: &str) -> bool {
+    Path::new(file)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("c8x"))
+}
 
-    f.read_exact(&mut rom_file)?;
+// How often the ROM file is looked at in watch mode.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+// Notices when the ROM file is rewritten, e.g. by an assembler running after every save.
+// Only the modification time is polled, which works the same on every platform.
+pub struct RomWatcher {
+    path: String,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl RomWatcher {
+    pub fn new(path: &str) -> Self {
+        RomWatcher {
+            path: path.to_string(),
+            modified: modified(path),
+            last_check: Instant::now(),
+        }
+    }
+
+    // True once after every change. Cheap enough to call every frame.
+    pub fn changed(&mut self) -> bool {
+        if self.last_check.elapsed() < WATCH_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        // A file that's gone for a moment is usually being replaced, wait until it's back.
+        modified.is_some()
+    }
+}
 
-    Ok(rom_file)
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
 }
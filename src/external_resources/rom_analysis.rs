@@ -0,0 +1,289 @@
+use crate::chip8::Quirks;
+
+// Instructions looked at after FX55/FX65 when checking whether the program relies on I moving on.
+const FOLLOW_UP_WINDOW: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl Confidence {
+    pub fn name(self) -> &'static str {
+        match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        }
+    }
+}
+
+// A guess at which interpreter a ROM was written for, for ROMs the database doesn't know.
+pub struct Analysis {
+    // "CHIP-8", "hi-res CHIP-8", "SUPER-CHIP" or "XO-CHIP".
+    pub platform: &'static str,
+    pub quirks: Quirks,
+    // The memory layout the platform needs, if it isn't the standard one.
+    pub memory_layout: Option<&'static str>,
+    pub confidence: Confidence,
+    // What the guess is based on, one finding per line.
+    pub notes: Vec<String>,
+}
+
+// Code and data are mixed in a CHIP-8 ROM and there is no way to tell them apart without running it,
+// so every aligned pair of bytes is treated as an instruction. Sprites that happen to look like
+// instructions are why a single hit never counts for much.
+pub fn analyse(rom: &[u8]) -> Analysis {
+    let opcodes: Vec<u16> = rom
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+
+    let mut notes = Vec::new();
+    let mut superchip = 0;
+    let mut xochip = 0;
+    let mut shifts_from_vy = 0;
+    let mut shifts_in_place = 0;
+    let mut load_store_chains = 0;
+    let mut jumps = 0;
+
+    for (i, &opcode) in opcodes.iter().enumerate() {
+        let x = (opcode >> 8) & 0xF;
+        let y = (opcode >> 4) & 0xF;
+        match (opcode >> 12, opcode & 0xF) {
+            // 00CN scroll down, 00FB-00FF scroll, exit and hi-res.
+            (0x0, _) if opcode & 0xFFF0 == 0x00C0 && opcode != 0x00C0 => superchip += 1,
+            (0x0, _) if (0x00FB..=0x00FF).contains(&opcode) => superchip += 1,
+            // 00DN scroll up.
+            (0x0, _) if opcode & 0xFFF0 == 0x00D0 && opcode != 0x00D0 => xochip += 1,
+            // 5XY2/5XY3 save and load a register range.
+            (0x5, 0x2) | (0x5, 0x3) => xochip += 1,
+            (0x8, 0x6) | (0x8, 0xE) => {
+                if x != y && y != 0 {
+                    shifts_from_vy += 1;
+                } else {
+                    shifts_in_place += 1;
+                }
+            }
+            (0xB, _) => jumps += 1,
+            // 16x16 sprites.
+            (0xD, 0x0) => superchip += 1,
+            (0xF, _) => match opcode & 0xFF {
+                // F000 NNNN long I, FN01 plane, F002 audio pattern.
+                0x00 if x == 0 => xochip += 1,
+                0x01 | 0x02 => xochip += 1,
+                // FX3A pitch.
+                0x3A => xochip += 1,
+                // FX30 big font, FX75/FX85 flag registers.
+                0x30 | 0x75 | 0x85 => superchip += 1,
+                0x55 | 0x65 if relies_on_incremented_i(&opcodes[i + 1..]) => load_store_chains += 1,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    // Hi-res programs bring part of their interpreter along, which is jumped over first.
+    let hires = opcodes.first() == Some(&0x1260) && rom.len() > 0xC0;
+
+    let (platform, confidence) = if hires {
+        ("hi-res CHIP-8", Confidence::High)
+    } else if xochip >= 3 {
+        ("XO-CHIP", Confidence::High)
+    } else if superchip >= 3 {
+        ("SUPER-CHIP", Confidence::High)
+    } else if xochip > 0 && xochip >= superchip {
+        ("XO-CHIP", Confidence::Low)
+    } else if superchip > 0 {
+        ("SUPER-CHIP", Confidence::Low)
+    } else if shifts_from_vy + shifts_in_place + load_store_chains > 0 {
+        ("CHIP-8", Confidence::Medium)
+    } else {
+        // Nothing to go on, the quirks stay at their defaults.
+        ("CHIP-8", Confidence::Low)
+    };
+    if superchip > 0 {
+        notes.push(format!("{} SUPER-CHIP instructions", superchip));
+    }
+    if xochip > 0 {
+        notes.push(format!("{} XO-CHIP instructions", xochip));
+    }
+    if hires {
+        notes.push("starts with 1260, the hi-res CHIP-8 interpreter".to_string());
+    }
+    if platform != "CHIP-8" && !hires {
+        notes.push(format!(
+            "{} instructions are not supported, the game may not run",
+            platform
+        ));
+    }
+
+    let mut quirks = Quirks::default();
+    match platform {
+        // Octo's XO-CHIP follows the VIP for shifts and FX55/FX65.
+        "XO-CHIP" => {
+            quirks.shift_reads_vy = true;
+            quirks.load_store_increments_i = true;
+        }
+        "SUPER-CHIP" => quirks.jump_uses_vx = jumps > 0,
+        // Written for the COSMAC VIP.
+        "hi-res CHIP-8" => {
+            quirks.display_wait = true;
+            quirks.shift_reads_vy = true;
+            quirks.load_store_increments_i = true;
+        }
+        _ => {
+            quirks.shift_reads_vy = shifts_from_vy > shifts_in_place;
+            quirks.load_store_increments_i = load_store_chains > 0;
+            // Anything that depends on VIP behaviour most likely also expects its display timing.
+            quirks.display_wait = quirks.shift_reads_vy || quirks.load_store_increments_i;
+        }
+    }
+
+    if shifts_from_vy + shifts_in_place > 0 {
+        notes.push(format!(
+            "{} shifts name a separate VY, {} don't, so shifts {}",
+            shifts_from_vy,
+            shifts_in_place,
+            if quirks.shift_reads_vy {
+                "read VY"
+            } else {
+                "work in place"
+            }
+        ));
+    }
+    if load_store_chains > 0 {
+        notes.push(format!(
+            "{} FX55/FX65 are followed by memory access without setting I, so I is incremented",
+            load_store_chains
+        ));
+    }
+    if jumps > 0 {
+        notes.push(format!(
+            "{} BNNN jumps, using {}",
+            jumps,
+            if quirks.jump_uses_vx { "VX" } else { "V0" }
+        ));
+    }
+    if notes.is_empty() {
+        notes.push("nothing platform specific found".to_string());
+    }
+
+    Analysis {
+        platform,
+        quirks,
+        memory_layout: if hires { Some("hires") } else { None },
+        confidence,
+        notes,
+    }
+}
+
+// Another memory access through I shortly after FX55/FX65, with no ANNN in between,
+// only makes sense if I was left pointing past the registers.
+fn relies_on_incremented_i(following: &[u16]) -> bool {
+    for &opcode in following.iter().take(FOLLOW_UP_WINDOW) {
+        match (opcode >> 12, opcode & 0xFF) {
+            (0xA, _) => return false,
+            (0xF, 0x29) => return false,
+            (0xF, 0x55) | (0xF, 0x65) | (0xF, 0x33) => return true,
+            // Jumps and calls leave the straight line of code.
+            (0x1, _) | (0x2, _) | (0xB, _) => return false,
+            _ if opcode == 0x00EE => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(opcodes: &[u16]) -> Vec<u8> {
+        opcodes
+            .iter()
+            .flat_map(|opcode| opcode.to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn nothing_to_go_on_keeps_the_defaults() {
+        let analysis = analyse(&rom(&[0x00E0, 0x6005, 0x1202]));
+        assert_eq!(analysis.platform, "CHIP-8");
+        assert_eq!(analysis.confidence, Confidence::Low);
+        assert_eq!(analysis.quirks, Quirks::default());
+        assert_eq!(analysis.notes, vec!["nothing platform specific found"]);
+    }
+
+    #[test]
+    fn counts_superchip_instructions() {
+        let one = analyse(&rom(&[0x00FF, 0x1202]));
+        assert_eq!(one.platform, "SUPER-CHIP");
+        assert_eq!(one.confidence, Confidence::Low);
+
+        // Hi-res, 16x16 sprite, big font, and a BNNN jump.
+        let three = analyse(&rom(&[0x00FF, 0xD010, 0xF030, 0xB300]));
+        assert_eq!(three.platform, "SUPER-CHIP");
+        assert_eq!(three.confidence, Confidence::High);
+        assert!(three.quirks.jump_uses_vx);
+        assert!(!three.quirks.shift_reads_vy);
+    }
+
+    #[test]
+    fn counts_xochip_instructions() {
+        let analysis = analyse(&rom(&[0x5012, 0x5013, 0xF201, 0x00FF]));
+        assert_eq!(analysis.platform, "XO-CHIP");
+        assert_eq!(analysis.confidence, Confidence::High);
+        assert!(analysis.quirks.shift_reads_vy);
+        assert!(analysis.quirks.load_store_increments_i);
+    }
+
+    #[test]
+    fn shifts_naming_vy_read_it() {
+        let analysis = analyse(&rom(&[0x8016, 0x823E, 0x8446]));
+        assert_eq!(analysis.confidence, Confidence::Medium);
+        assert!(analysis.quirks.shift_reads_vy);
+        assert!(analysis.quirks.display_wait);
+
+        // 8XX6 and 8X06 work the same either way.
+        let in_place = analyse(&rom(&[0x8006, 0x8116, 0x8446]));
+        assert!(!in_place.quirks.shift_reads_vy);
+    }
+
+    #[test]
+    fn load_store_chains_without_annn_increment_i() {
+        let chained = analyse(&rom(&[0xA300, 0xF255, 0x6001, 0xF265]));
+        assert!(chained.quirks.load_store_increments_i);
+
+        let reloaded = analyse(&rom(&[0xA300, 0xF255, 0xA300, 0xF265]));
+        assert!(!reloaded.quirks.load_store_increments_i);
+
+        // Too far apart to count.
+        let distant = analyse(&rom(&[0xF255, 0x6001, 0x6002, 0x6003, 0xF265]));
+        assert!(!distant.quirks.load_store_increments_i);
+    }
+
+    #[test]
+    fn bnnn_on_plain_chip8_uses_v0() {
+        let analysis = analyse(&rom(&[0xB300]));
+        assert!(!analysis.quirks.jump_uses_vx);
+        assert!(analysis
+            .notes
+            .contains(&"1 BNNN jumps, using V0".to_string()));
+    }
+
+    #[test]
+    fn a_leading_1260_means_hires() {
+        let mut program = rom(&[0x1260]);
+        program.resize(0x100, 0);
+        let analysis = analyse(&program);
+        assert_eq!(analysis.platform, "hi-res CHIP-8");
+        assert_eq!(analysis.memory_layout, Some("hires"));
+        assert!(analysis.quirks.display_wait);
+
+        // Too short to hold the interpreter part, so just a jump.
+        assert_eq!(analyse(&rom(&[0x1260])).memory_layout, None);
+    }
+}
@@ -0,0 +1,145 @@
+use std::fs;
+use std::path::Path;
+
+use super::config_dir::config_dir;
+use super::osd::Menu;
+
+pub const ROM_EXTENSIONS: [&str; 5] = ["ch8", "c8", "c8x", "sc8", "xo8"];
+
+const RECENT_FILE_NAME: &str = "recent";
+const RECENT_LIMIT: usize = 10;
+// How many entries fit on screen at once.
+const VISIBLE_ROWS: usize = 12;
+
+// The menu shown when the emulator is started without a ROM.
+// Recently played games come first, then every ROM in the directory. Driven by the CHIP-8 keypad:
+// 2 and 8 move, 4 and 6 jump a page, 5 starts the selected game.
+pub struct RomBrowser {
+    // (label, path)
+    entries: Vec<(String, String)>,
+    selected: usize,
+    previous_keys: [bool; 16],
+}
+
+impl RomBrowser {
+    pub fn new(dir: &str) -> Result<Self, String> {
+        let mut entries: Vec<(String, String)> = load_recent()
+            .into_iter()
+            .filter(|path| Path::new(path).exists())
+            .map(|path| (format!("* {}", file_name(&path)), path))
+            .collect();
+
+        let mut roms = Vec::new();
+        let listing = fs::read_dir(dir).map_err(|e| format!("cannot read {}: {}", dir, e))?;
+        for entry in listing.flatten() {
+            let path = entry.path();
+            if is_rom(&path) {
+                roms.push(path.to_string_lossy().to_string());
+            }
+        }
+        roms.sort();
+        entries.extend(roms.into_iter().map(|path| (file_name(&path), path)));
+
+        Ok(RomBrowser {
+            entries,
+            selected: 0,
+            previous_keys: [false; 16],
+        })
+    }
+
+    // Moves the selection on fresh key presses. Returns the ROM to start once one is picked.
+    pub fn update(&mut self, keys: [bool; 16]) -> Option<String> {
+        let previous = self.previous_keys;
+        let pressed = |key: usize| keys[key] && !previous[key];
+        let last = self.entries.len().saturating_sub(1);
+        let mut chosen = None;
+
+        if pressed(0x2) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if pressed(0x8) {
+            self.selected = (self.selected + 1).min(last);
+        }
+        if pressed(0x4) {
+            self.selected = self.selected.saturating_sub(VISIBLE_ROWS);
+        }
+        if pressed(0x6) {
+            self.selected = (self.selected + VISIBLE_ROWS).min(last);
+        }
+        if pressed(0x5) {
+            chosen = self
+                .entries
+                .get(self.selected)
+                .map(|(_, path)| path.clone());
+        }
+
+        self.previous_keys = keys;
+        chosen
+    }
+
+    // The part of the list around the selection, ready for the on-screen display.
+    pub fn menu(&self) -> Menu {
+        if self.entries.is_empty() {
+            return Menu {
+                title: "No ROMs found - drop one on the window".to_string(),
+                items: Vec::new(),
+                selected: 0,
+            };
+        }
+
+        let first = self.selected.saturating_sub(VISIBLE_ROWS / 2);
+        let first = first.min(self.entries.len().saturating_sub(VISIBLE_ROWS));
+        Menu {
+            title: "Select a ROM".to_string(),
+            items: self.entries[first..]
+                .iter()
+                .take(VISIBLE_ROWS)
+                .map(|(label, _)| label.clone())
+                .collect(),
+            selected: self.selected - first,
+        }
+    }
+}
+
+pub fn is_rom(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ROM_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+// Most recent first, one path per line in the config directory.
+fn load_recent() -> Vec<String> {
+    let path = match config_dir() {
+        Some(dir) => dir.join(RECENT_FILE_NAME),
+        None => return Vec::new(),
+    };
+    fs::read_to_string(path)
+        .map(|content| content.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+// Moves the ROM to the top of the recently played list.
+pub fn add_recent(rom_path: &str) -> Result<(), String> {
+    let dir = config_dir().ok_or_else(|| "no config directory".to_string())?;
+    // Stored as absolute paths, so the list works no matter where the emulator is started from.
+    let rom_path = fs::canonicalize(rom_path)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| rom_path.to_string());
+
+    let mut recent = load_recent();
+    recent.retain(|path| *path != rom_path);
+    recent.insert(0, rom_path);
+    recent.truncate(RECENT_LIMIT);
+
+    let path = dir.join(RECENT_FILE_NAME);
+    fs::write(&path, recent.join("\n") + "\n")
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
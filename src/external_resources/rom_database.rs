@@ -0,0 +1,211 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+use super::config_dir::config_dir;
+use super::controller::ControllerMapping;
+use super::memory_layout;
+use super::palette::{self, Palette};
+use crate::chip8::Quirks;
+
+// Uses the layout of the community CHIP-8 database (github.com/chip-8/chip-8-database):
+// a list of programs, each with its ROM versions keyed by the SHA-1 of the file.
+// data/programs.json is built in, a vendored copy of the community database with its licence next to it.
+const BUNDLED: &str = include_str!("../../data/programs.json");
+// Your own entries, or a newer copy of the full database, can go here and are searched first.
+const USER_FILE_NAME: &str = "programs.json";
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize)]
+struct RomEntry {
+    #[serde(default)]
+    platforms: Vec<String>,
+    tickrate: Option<u32>,
+    colors: Option<Colors>,
+    #[serde(default)]
+    keys: HashMap<String, usize>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+// What the database knows about a ROM.
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    // The first platform the ROM is known to run on, e.g. "originalChip8" or "superchip".
+    pub platform: Option<String>,
+    // Instructions per frame.
+    pub tickrate: Option<u32>,
+    pub colors: Vec<(u8, u8, u8)>,
+    // Which CHIP-8 key does "up", "down", "left", "right", "a" and "b".
+    pub keys: Vec<(String, usize)>,
+}
+
+impl RomInfo {
+    // None if the ROM is in neither the user's database nor the bundled one.
+    pub fn lookup(rom: &[u8]) -> Result<Option<RomInfo>, String> {
+        let hash = sha1_smol::Sha1::from(rom).digest().to_string();
+
+        if let Some(path) = config_dir().map(|dir| dir.join(USER_FILE_NAME)) {
+            if path.exists() {
+                let content = fs::read_to_string(&path)
+                    .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+                let info = find(&content, &hash)
+                    .map_err(|e| format!("invalid {}: {}", path.display(), e))?;
+                if info.is_some() {
+                    return Ok(info);
+                }
+            }
+        }
+
+        find(BUNDLED, &hash).map_err(|e| format!("invalid ROM database: {}", e))
+    }
+
+    // Quirks of the interpreter the ROM was written for.
+    pub fn quirks(&self) -> Option<Quirks> {
+        let platform = self.platform.as_deref()?;
+        let vip = matches!(platform, "originalChip8" | "hybridVIP" | "chip8x");
+        Some(Quirks {
+            display_wait: vip,
+            shift_reads_vy: vip || platform == "xochip",
+            load_store_increments_i: vip || platform == "xochip",
+            jump_uses_vx: platform.starts_with("superchip") || platform == "chip48",
+        })
+    }
+
+    // The memory layout of the platform, for platforms that differ from the standard one.
+    // User entries can also name a layout directly, e.g. "eti660", "hires" or one from "memory-layouts".
+    pub fn memory_layout(&self) -> Option<&str> {
+        match self.platform.as_deref()? {
+            "originalChip8" | "hybridVIP" => Some("vip"),
+            platform if memory_layout::lookup(platform).is_ok() => Some(platform),
+            _ => None,
+        }
+    }
+
+    // The platform as people know it, e.g. "SUPER-CHIP 1.1" for "superchip".
+    pub fn platform_name(&self) -> Option<&str> {
+        let name = match self.platform.as_deref()? {
+            "originalChip8" | "modernChip8" => "CHIP-8",
+            "hybridVIP" => "CHIP-8 with VIP code",
+            "chip8x" => "CHIP-8X",
+            "chip48" => "CHIP-48",
+            "superchip1" => "SUPER-CHIP 1.0",
+            "superchip" => "SUPER-CHIP 1.1",
+            "megachip8" => "MEGA-CHIP",
+            "xochip" => "XO-CHIP",
+            other => other,
+        };
+        Some(name)
+    }
+
+    pub fn palette(&self) -> Option<Palette> {
+        let background = *self.colors.first()?;
+        let foreground = *self.colors.get(1)?;
+        Some(Palette {
+            name: self.title.clone(),
+            colors: [
+                background,
+                foreground,
+                *self.colors.get(2).unwrap_or(&foreground),
+                *self.colors.get(3).unwrap_or(&foreground),
+            ],
+        })
+    }
+
+    pub fn controller_mapping(&self) -> Option<ControllerMapping> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        Some(ControllerMapping::from_hints(&self.keys))
+    }
+
+    // "Pong by Paul Vervalin"
+    pub fn description(&self) -> String {
+        if self.authors.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{} by {}", self.title, self.authors.join(", "))
+        }
+    }
+}
+
+fn find(content: &str, hash: &str) -> Result<Option<RomInfo>, serde_json::Error> {
+    let programs: Vec<Program> = serde_json::from_str(content)?;
+
+    for program in programs {
+        if let Some(rom) = program.roms.get(hash) {
+            let mut keys: Vec<(String, usize)> = rom
+                .keys
+                .iter()
+                .filter(|&(_, &key)| key <= 0xF)
+                .map(|(name, &key)| (name.clone(), key))
+                .collect();
+            keys.sort();
+
+            return Ok(Some(RomInfo {
+                title: program.title,
+                authors: program.authors,
+                platform: rom.platforms.first().cloned(),
+                tickrate: rom.tickrate.filter(|&t| t > 0),
+                colors: rom
+                    .colors
+                    .iter()
+                    .flat_map(|c| c.pixels.iter())
+                    .filter_map(|c| palette::parse_color(c))
+                    .collect(),
+                keys,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_bundled_database_parses() {
+        assert!(find(BUNDLED, "").is_ok());
+    }
+
+    #[test]
+    fn finds_a_rom_by_hash() {
+        let content = r##"[{
+            "title": "Pong",
+            "authors": ["Paul Vervalin"],
+            "roms": {
+                "abc": {
+                    "platforms": ["originalChip8"],
+                    "tickrate": 15,
+                    "colors": {"pixels": ["#000000", "#ffb000"]},
+                    "keys": {"up": 1, "down": 4, "bogus": 99}
+                }
+            }
+        }]"##;
+
+        let info = find(content, "abc").unwrap().unwrap();
+        assert_eq!(info.description(), "Pong by Paul Vervalin");
+        assert_eq!(info.tickrate, Some(15));
+        assert_eq!(info.memory_layout(), Some("vip"));
+        assert_eq!(info.palette().unwrap().colors[1], (0xFF, 0xB0, 0x00));
+        assert_eq!(
+            info.keys,
+            vec![("down".to_string(), 4), ("up".to_string(), 1)]
+        );
+        assert!(find(content, "def").unwrap().is_none());
+    }
+}
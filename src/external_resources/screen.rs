@@ -1,33 +1,240 @@
-use sdl2;
-use sdl2::pixels;
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
+use sdl2::video::{FullscreenType, Window};
 
-use super::SCALE_FACTOR;
+use super::display::Display;
+use super::filters;
+use super::image::Image;
+use super::osd::Osd;
+use super::palette::{Palette, PaletteSet, CHIP8X_COLORS};
+use super::recording::{Recording, RECORDING_DIR};
+use super::screenshot;
+use super::window_state::WindowState;
+use super::Hotkey;
+use crate::chip8::ColorLayer;
 
-impl super::Screen {
-    pub fn draw(&mut self, pixels: &[[u8; 64]; 32]) -> Result<(), String> {
-        for (y, row) in pixels.iter().enumerate() {
-            for (x, &col) in row.iter().enumerate() {
-                let x = (x as u32) * SCALE_FACTOR;
-                let y = (y as u32) * SCALE_FACTOR;
+impl Display for super::Screen {
+    // Just remembers the framebuffer, it's turned into pixels once per frame in `present`.
+    fn draw(&mut self, pixels: &[[u8; 64]]) -> Result<(), String> {
+        if pixels.len() != self.frame.len() {
+            self.resize_texture(pixels.len())?;
+        }
+        self.frame.clear();
+        self.frame.extend_from_slice(pixels);
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), String> {
+        // The persistence filter has to run every frame, even if nothing was drawn, so fades keep going.
+        let pixels = self.frame.as_flattened();
+        let levels = self.persistence.apply(pixels);
+        let image = Image::from_framebuffer(
+            pixels,
+            64,
+            Some(levels),
+            self.palettes.current(),
+            self.colors.as_ref(),
+        );
+        let image = filters::apply_chain(&self.filters, image);
 
-                let color = color(col);
+        self.texture
+            .with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                for (y, row) in image.pixels.chunks(image.width).enumerate() {
+                    for (x, &(r, g, b)) in row.iter().enumerate() {
+                        let offset = y * pitch + x * 3;
+                        buffer[offset] = r;
+                        buffer[offset + 1] = g;
+                        buffer[offset + 2] = b;
+                    }
+                }
+            })
+            .map_err(|e| format!("cannot update texture: {}", e))?;
+        self.last_image = image;
 
-                self.canvas.set_draw_color(color);
+        // The bars around the picture match its background, which CHIP-8X ROMs choose themselves.
+        let (r, g, b) = match &self.colors {
+            Some(colors) => CHIP8X_COLORS[colors.background as usize & 7],
+            None => self.palettes.current().colors[0],
+        };
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.clear();
+
+        let output_size = self.canvas.output_size()?;
+        let target = letterbox(output_size, self.texture_size, self.integer_scale);
+        self.canvas
+            .copy(&self.texture, None, target)
+            .map_err(|e| format!("cannot draw texture: {}", e))?;
+        // Drawn straight on the canvas, so it never ends up in screenshots or recordings.
+        self.osd.render(&mut self.canvas)?;
+        self.canvas.present();
+        Ok(())
+    }
+
+    // Recordings get the CHIP-8 screen of every emulated frame, without the persistence fades,
+    // which follow the host's frames.
+    fn end_frame(&mut self) {
+        let recording = match &mut self.recording {
+            Some(recording) => recording,
+            None => return,
+        };
+        let image = Image::from_framebuffer(
+            self.frame.as_flattened(),
+            64,
+            None,
+            self.palettes.current(),
+            self.colors.as_ref(),
+        );
+        let image = filters::apply_chain(&self.filters, image);
+        // The ROM switched resolution or the clip is full: what's there is saved
+        // and the recording goes on in a new file.
+        let reason = match recording.push(&image) {
+            Ok(()) => return,
+            Err(reason) => reason,
+        };
+        let finished = std::mem::take(recording);
+        let _ = recording.push(&image);
+        let message = match self.save_recording(&finished) {
+            Ok(()) => format!("Recording saved, {}. Continuing in a new file", reason),
+            Err(e) => e,
+        };
+        self.osd.show_message(&message);
+    }
 
-                self.canvas
-                    .fill_rect(Rect::new(x as i32, y as i32, SCALE_FACTOR, SCALE_FACTOR))
-                    .or_else(|e| Err(format!("cannot draw rectangle: {}", e)))?;
+    fn hotkey(&mut self, hotkey: Hotkey) -> Result<(), String> {
+        match hotkey {
+            Hotkey::CyclePersistence => {
+                self.persistence.mode = self.persistence.mode.next(self.fade_frames);
             }
+            Hotkey::CyclePalette => {
+                let palette = self.palettes.next();
+                if let Some(path) = &self.palette_path {
+                    palette.save(path)?;
+                }
+                self.osd.show_message(&format!("Palette: {}", palette.name));
+            }
+            Hotkey::ToggleFullscreen => {
+                let window = self.canvas.window_mut();
+                let fullscreen = if window.fullscreen_state() == FullscreenType::Off {
+                    self.windowed = window_geometry(window);
+                    FullscreenType::Desktop
+                } else {
+                    FullscreenType::Off
+                };
+                window.set_fullscreen(fullscreen)?;
+            }
+            Hotkey::Screenshot => {
+                let path =
+                    screenshot::next_path(screenshot::SCREENSHOT_DIR, &self.rom_name, "png")?;
+                screenshot::save_png(&self.last_image.scaled(self.screenshot_scale), &path)?;
+                self.osd.show_message("Screenshot saved");
+            }
+            Hotkey::ToggleRecording => match self.recording.take() {
+                Some(recording) => {
+                    self.save_recording(&recording)?;
+                    self.osd.show_message("Recording saved");
+                }
+                None => {
+                    self.recording = Some(Recording::default());
+                    self.osd.show_message("Recording");
+                }
+            },
+            Hotkey::ToggleStats => self.osd.show_stats = !self.osd.show_stats,
+            _ => {}
         }
         Ok(())
     }
+
+    fn set_title(&mut self, title: &str) -> Result<(), String> {
+        self.canvas
+            .window_mut()
+            .set_title(title)
+            .map_err(|e| format!("cannot set window title: {}", e))
+    }
+
+    fn set_colors(&mut self, colors: Option<&ColorLayer>) {
+        self.colors = colors.copied();
+    }
+
+    fn set_rom(&mut self, rom_name: &str, palette: Palette, palette_path: String) {
+        self.rom_name = rom_name.to_string();
+        self.palettes = PaletteSet::new(palette);
+        self.palette_path = Some(palette_path);
+    }
+
+    fn osd(&mut self) -> Option<&mut Osd> {
+        Some(&mut self.osd)
+    }
+}
+
+impl super::Screen {
+    // The ROM switched between the normal and the hi-res screen.
+    fn resize_texture(&mut self, rows: usize) -> Result<(), String> {
+        let (width, height) = filters::chain_size(&self.filters, 64, rows);
+        self.texture = self
+            .canvas
+            .texture_creator()
+            .create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32)
+            .map_err(|e| format!("can't create texture: {}", e))?;
+        self.texture_size = (width as u32, height as u32);
+        Ok(())
+    }
+
+    fn save_recording(&self, recording: &Recording) -> Result<(), String> {
+        let path = screenshot::next_path(
+            RECORDING_DIR,
+            &self.rom_name,
+            self.recording_format.extension(),
+        )?;
+        recording.save(&path, self.recording_format)
+    }
 }
 
-fn color(value: u8) -> pixels::Color {
-    if value == 0 {
-        pixels::Color::RGB(0, 0, 0)
-    } else {
-        pixels::Color::RGB(0, 250, 0)
+impl Drop for super::Screen {
+    fn drop(&mut self) {
+        // Closing the window in the middle of a recording shouldn't throw it away.
+        if let Some(recording) = self.recording.take() {
+            let _ = self.save_recording(&recording);
+        }
+
+        let window = self.canvas.window();
+        let state = if window.fullscreen_state() == FullscreenType::Off {
+            window_geometry(window)
+        } else {
+            self.windowed
+        };
+        // Losing the window position is not worth failing over.
+        let _ = state.save();
+    }
+}
+
+fn window_geometry(window: &Window) -> WindowState {
+    let (x, y) = window.position();
+    let (width, height) = window.size();
+    WindowState {
+        x,
+        y,
+        width,
+        height,
     }
 }
+
+// The largest rectangle with the texture's aspect ratio that fits the window, centered.
+// The rest of the window stays in the background colour.
+fn letterbox(output: (u32, u32), texture: (u32, u32), integer_scale: bool) -> Rect {
+    let scale_x = output.0 as f32 / texture.0 as f32;
+    let scale_y = output.1 as f32 / texture.1 as f32;
+    let mut scale = scale_x.min(scale_y);
+    // A window smaller than the texture still has to show something.
+    if integer_scale && scale >= 1.0 {
+        scale = scale.floor();
+    }
+
+    let width = ((texture.0 as f32 * scale) as u32).max(1);
+    let height = ((texture.1 as f32 * scale) as u32).max(1);
+    Rect::new(
+        (output.0.saturating_sub(width) / 2) as i32,
+        (output.1.saturating_sub(height) / 2) as i32,
+        width,
+        height,
+    )
+}
@@ -0,0 +1,55 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::filters::{self, Filter};
+use super::image::Image;
+use super::palette::Palette;
+use crate::chip8::ColorLayer;
+
+pub const SCREENSHOT_DIR: &str = "screenshots";
+
+// The framebuffer as a picture in the given palette, run through the filters like on screen,
+// then `scale` times bigger.
+pub fn capture(
+    pixels: &[[u8; 64]],
+    colors: Option<&ColorLayer>,
+    palette: &Palette,
+    filters: &[Filter],
+    scale: usize,
+) -> Image {
+    let image = Image::from_framebuffer(pixels.as_flattened(), 64, None, palette, colors);
+    filters::apply_chain(filters, image).scaled(scale)
+}
+
+pub fn save_png(image: &Image, path: &Path) -> Result<(), String> {
+    let file =
+        File::create(path).map_err(|e| format!("cannot create {}: {}", path.display(), e))?;
+
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        image.width as u32,
+        image.height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&image.to_rgb_bytes()))
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+// "screenshots/pong.ch8-1700000000123.png", creating the directory if needed.
+// The timestamp is in milliseconds, so screenshots taken in quick succession don't overwrite each other.
+pub fn next_path(dir: &str, rom_name: &str, extension: &str) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir, e))?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+
+    Ok(Path::new(dir).join(format!("{}-{}.{}", rom_name, timestamp, extension)))
+}
@@ -0,0 +1,95 @@
+use super::config_file::for_each_line;
+use super::input_source::InputSource;
+
+enum Action {
+    Press(usize),
+    Release(usize),
+    PressSecond(usize),
+    ReleaseSecond(usize),
+    Quit,
+}
+
+// Replays key events from a text file. Every line is "<frame> <hex key> down|up" or "<frame> quit",
+// frames count from 0 and lines must be in order. Empty lines and lines starting with '#' are ignored.
+// "down2" and "up2" press and release keys on the CHIP-8X's second keypad.
+//
+//     # hold 5 for half a second, then stop after ten seconds
+//     60 5 down
+//     90 5 up
+//     600 quit
+#[derive(Default)]
+pub struct ScriptedInput {
+    events: Vec<(u64, Action)>,
+    next_event: usize,
+    frame: u64,
+    keys: [bool; 16],
+    second_keys: [bool; 16],
+}
+
+impl ScriptedInput {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut events: Vec<(u64, Action)> = Vec::new();
+        for_each_line(path, |line| {
+            let event = parse_event(line).ok_or_else(|| format!("invalid event '{}'", line))?;
+            if let Some(&(last_frame, _)) = events.last() {
+                if event.0 < last_frame {
+                    return Err("events are out of order".to_string());
+                }
+            }
+            events.push(event);
+            Ok(())
+        })?;
+
+        Ok(ScriptedInput {
+            events,
+            ..ScriptedInput::default()
+        })
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll(&mut self) -> Result<[bool; 16], String> {
+        while let Some((frame, action)) = self.events.get(self.next_event) {
+            if *frame > self.frame {
+                break;
+            }
+            match *action {
+                Action::Press(key) => self.keys[key] = true,
+                Action::Release(key) => self.keys[key] = false,
+                Action::PressSecond(key) => self.second_keys[key] = true,
+                Action::ReleaseSecond(key) => self.second_keys[key] = false,
+                Action::Quit => return Err("input script finished".to_string()),
+            }
+            self.next_event += 1;
+        }
+
+        self.frame += 1;
+        Ok(self.keys)
+    }
+
+    fn second_keypad(&self) -> [bool; 16] {
+        self.second_keys
+    }
+}
+
+fn parse_event(line: &str) -> Option<(u64, Action)> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let frame = parts.first()?.parse().ok()?;
+
+    let action = match parts[1..] {
+        ["quit"] => Action::Quit,
+        [key, state] => {
+            let key = usize::from_str_radix(key, 16).ok().filter(|&k| k <= 0xF)?;
+            match state {
+                "down" => Action::Press(key),
+                "up" => Action::Release(key),
+                "down2" => Action::PressSecond(key),
+                "up2" => Action::ReleaseSecond(key),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    Some((frame, action))
+}
@@ -0,0 +1,102 @@
+use std::io::{self, BufRead, BufReader};
+use std::net::{TcpListener, TcpStream};
+
+use super::input_source::InputSource;
+
+// Lets a bot press keys over a plain TCP connection on localhost.
+// The protocol is line based: "down <hex key>", "up <hex key>" or "quit".
+// "down2" and "up2" do the same on the CHIP-8X's second keypad.
+// One client at a time; when it disconnects all keys are released and the next one can connect.
+pub struct SocketInput {
+    listener: TcpListener,
+    client: Option<BufReader<TcpStream>>,
+    line: String,
+    keys: [bool; 16],
+    second_keys: [bool; 16],
+}
+
+impl SocketInput {
+    pub fn new(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("cannot listen on port {}: {}", port, e))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("cannot set up socket: {}", e))?;
+
+        Ok(SocketInput {
+            listener,
+            client: None,
+            line: String::new(),
+            keys: [false; 16],
+            second_keys: [false; 16],
+        })
+    }
+
+    // Applies every complete line the client has sent so far.
+    fn read_commands(&mut self) -> Result<(), String> {
+        let client = match self.client.as_mut() {
+            Some(client) => client,
+            None => return Ok(()),
+        };
+
+        loop {
+            match client.read_line(&mut self.line) {
+                Ok(0) => {
+                    self.client = None;
+                    self.keys = [false; 16];
+                    self.second_keys = [false; 16];
+                    return Ok(());
+                }
+                Ok(_) => {
+                    if !self.line.ends_with('\n') {
+                        // Partial line, the rest will come later.
+                        continue;
+                    }
+                    let mut parts = self.line.split_whitespace();
+                    match (parts.next(), parts.next().and_then(parse_key)) {
+                        (Some("down"), Some(key)) => self.keys[key] = true,
+                        (Some("up"), Some(key)) => self.keys[key] = false,
+                        (Some("down2"), Some(key)) => self.second_keys[key] = true,
+                        (Some("up2"), Some(key)) => self.second_keys[key] = false,
+                        (Some("quit"), None) => return Err("socket client quit".to_string()),
+                        // Nobody is reading replies, so garbage is just skipped.
+                        _ => {}
+                    }
+                    self.line.clear();
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(_) => {
+                    self.client = None;
+                    self.keys = [false; 16];
+                    self.second_keys = [false; 16];
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl InputSource for SocketInput {
+    fn poll(&mut self) -> Result<[bool; 16], String> {
+        if self.client.is_none() {
+            if let Ok((stream, _)) = self.listener.accept() {
+                stream
+                    .set_nonblocking(true)
+                    .map_err(|e| format!("cannot set up socket: {}", e))?;
+                self.client = Some(BufReader::new(stream));
+                self.line.clear();
+            }
+        }
+
+        self.read_commands()?;
+        Ok(self.keys)
+    }
+
+    fn second_keypad(&self) -> [bool; 16] {
+        self.second_keys
+    }
+}
+
+fn parse_key(key: &str) -> Option<usize> {
+    usize::from_str_radix(key, 16).ok().filter(|&k| k <= 0xF)
+}
@@ -0,0 +1,83 @@
+use std::io::prelude::*;
+use std::io::{self, Stdout};
+
+use super::display::Display;
+
+// Every character cell covers two pixel rows, so the whole 64x32 screen fits into 64x16 cells
+// (64x32 cells in hi-res).
+const UPPER_HALF: char = '\u{2580}';
+const LOWER_HALF: char = '\u{2584}';
+const FULL_BLOCK: char = '\u{2588}';
+
+const CLEAR_SCREEN: &str = "\x1b[2J";
+const CURSOR_HOME: &str = "\x1b[H";
+const CLEAR_TO_END: &str = "\x1b[J";
+const HIDE_CURSOR: &str = "\x1b[?25l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+
+// Draws the screen with Unicode half blocks, so games can be played over SSH.
+pub struct TerminalDisplay {
+    out: Stdout,
+    frame: String,
+}
+
+impl TerminalDisplay {
+    pub fn new() -> Result<Self, String> {
+        let mut out = io::stdout();
+        write!(out, "{}{}", CLEAR_SCREEN, HIDE_CURSOR)
+            .and_then(|_| out.flush())
+            .map_err(|e| format!("cannot write to terminal: {}", e))?;
+
+        Ok(TerminalDisplay {
+            out,
+            frame: String::new(),
+        })
+    }
+}
+
+impl Display for TerminalDisplay {
+    fn draw(&mut self, pixels: &[[u8; 64]]) -> Result<(), String> {
+        // Build the whole frame first, the terminal flickers a lot less with a single write.
+        let frame = &mut self.frame;
+        frame.clear();
+        frame.push_str(CURSOR_HOME);
+
+        for rows in pixels.chunks(2) {
+            for (&upper, &lower) in rows[0].iter().zip(rows[1].iter()) {
+                let cell = match (upper != 0, lower != 0) {
+                    (false, false) => ' ',
+                    (true, false) => UPPER_HALF,
+                    (false, true) => LOWER_HALF,
+                    (true, true) => FULL_BLOCK,
+                };
+                frame.push(cell);
+            }
+            frame.push_str("\r\n");
+        }
+        // Leaving hi-res draws fewer rows, the rest of the taller frame has to go.
+        frame.push_str(CLEAR_TO_END);
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), String> {
+        if self.frame.is_empty() {
+            return Ok(());
+        }
+
+        let result = self
+            .out
+            .write_all(self.frame.as_bytes())
+            .and_then(|_| self.out.flush())
+            .map_err(|e| format!("cannot write to terminal: {}", e));
+        // Nothing changed until the next draw, so there is nothing to write either.
+        self.frame.clear();
+        result
+    }
+}
+
+impl Drop for TerminalDisplay {
+    fn drop(&mut self) {
+        let _ = write!(self.out, "{}", SHOW_CURSOR);
+        let _ = self.out.flush();
+    }
+}
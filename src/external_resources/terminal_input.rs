@@ -0,0 +1,112 @@
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use super::input_source::{keypad_index, InputSource};
+use super::Hotkey;
+
+// Terminals only report key presses, never releases, so a key counts as held
+// for this many frames after its last press. Auto-repeat keeps held keys alive.
+const HOLD_FRAMES: u8 = 6;
+
+const CTRL_C: u8 = 0x03;
+const ESCAPE: u8 = 0x1b;
+const BACKSPACE: u8 = 0x7f;
+
+// Reads the keyboard from a terminal in raw mode, to pair with the terminal display.
+pub struct TerminalInput {
+    keys: Receiver<u8>,
+    held: [u8; 16],
+    hotkeys: Vec<Hotkey>,
+    saved_mode: String,
+}
+
+impl TerminalInput {
+    pub fn new() -> Result<Self, String> {
+        let saved_mode = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+
+        // Reading stdin blocks, so it happens on its own thread and the emulator picks up whatever arrived.
+        let (sender, keys) = mpsc::channel();
+        thread::spawn(move || {
+            for byte in io::stdin().lock().bytes() {
+                match byte {
+                    Ok(byte) => {
+                        if sender.send(byte).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(TerminalInput {
+            keys,
+            held: [0; 16],
+            hotkeys: Vec::new(),
+            saved_mode: saved_mode.trim().to_string(),
+        })
+    }
+}
+
+impl InputSource for TerminalInput {
+    fn poll(&mut self) -> Result<[bool; 16], String> {
+        self.hotkeys.clear();
+        for frames in self.held.iter_mut() {
+            *frames = frames.saturating_sub(1);
+        }
+
+        while let Ok(byte) = self.keys.try_recv() {
+            match byte {
+                CTRL_C | ESCAPE => return Err("terminal input closed".to_string()),
+                b'm' | b'M' => self.hotkeys.push(Hotkey::ToggleMute),
+                b'p' | b'P' => self.hotkeys.push(Hotkey::CyclePersistence),
+                b'o' | b'O' => self.hotkeys.push(Hotkey::CyclePalette),
+                b' ' => self.hotkeys.push(Hotkey::Pause),
+                BACKSPACE => self.hotkeys.push(Hotkey::Reset),
+                b'+' | b'=' => self.hotkeys.push(Hotkey::SpeedUp),
+                b'-' => self.hotkeys.push(Hotkey::SpeedDown),
+                b'.' => self.hotkeys.push(Hotkey::FrameAdvance),
+                _ => {
+                    if let Some(i) = keypad_index(byte as char) {
+                        self.held[i] = HOLD_FRAMES;
+                    }
+                }
+            }
+        }
+
+        let mut chip8_keys = [false; 16];
+        for (key, &frames) in chip8_keys.iter_mut().zip(self.held.iter()) {
+            *key = frames > 0;
+        }
+        Ok(chip8_keys)
+    }
+
+    fn hotkeys(&self) -> &[Hotkey] {
+        &self.hotkeys
+    }
+}
+
+impl Drop for TerminalInput {
+    fn drop(&mut self) {
+        let _ = stty(&[self.saved_mode.as_str()]);
+    }
+}
+
+fn stty(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("cannot run stty: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "stty failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
@@ -0,0 +1,63 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{BufWriter, Error};
+
+use super::audio::{ToneGenerator, ToneSettings};
+
+const SAMPLE_RATE: u32 = 44_100;
+const FRAMES_PER_SECOND: u32 = 60;
+
+// Renders the buzzer into memory instead of a sound card.
+// Time only moves forward when the emulator finishes a frame, so two runs of the same ROM
+// with the same input always give the same samples no matter how fast the host is.
+pub struct WavRecorder {
+    generator: ToneGenerator,
+    samples: Vec<i16>,
+}
+
+impl WavRecorder {
+    pub fn new(settings: ToneSettings) -> Self {
+        WavRecorder {
+            generator: ToneGenerator::new(settings, SAMPLE_RATE),
+            samples: Vec::new(),
+        }
+    }
+
+    // Appends exactly one 60 Hz frame worth of audio.
+    pub fn record_frame(&mut self, playing: bool) {
+        self.generator.gate = playing;
+        for _ in 0..SAMPLE_RATE / FRAMES_PER_SECOND {
+            let sample = self.generator.next_sample();
+            self.samples.push((sample * i16::MAX as f32) as i16);
+        }
+    }
+
+    // 16-bit mono PCM.
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        let mut f = BufWriter::new(File::create(path)?);
+
+        let data_size = (self.samples.len() * 2) as u32;
+        let byte_rate = SAMPLE_RATE * 2;
+
+        f.write_all(b"RIFF")?;
+        f.write_all(&(36 + data_size).to_le_bytes())?;
+        f.write_all(b"WAVE")?;
+
+        f.write_all(b"fmt ")?;
+        f.write_all(&16u32.to_le_bytes())?; // chunk size
+        f.write_all(&1u16.to_le_bytes())?; // PCM
+        f.write_all(&1u16.to_le_bytes())?; // channels
+        f.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        f.write_all(&byte_rate.to_le_bytes())?;
+        f.write_all(&2u16.to_le_bytes())?; // block align
+        f.write_all(&16u16.to_le_bytes())?; // bits per sample
+
+        f.write_all(b"data")?;
+        f.write_all(&data_size.to_le_bytes())?;
+        for sample in &self.samples {
+            f.write_all(&sample.to_le_bytes())?;
+        }
+
+        f.flush()
+    }
+}
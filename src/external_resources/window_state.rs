@@ -0,0 +1,44 @@
+use std::fs;
+
+use super::config_dir::config_dir;
+
+const FILE_NAME: &str = "window";
+
+// Size and position of the window, remembered between runs.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct WindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl WindowState {
+    // A missing or broken file just means the window starts with the defaults.
+    pub fn load() -> Option<WindowState> {
+        let content = fs::read_to_string(config_dir()?.join(FILE_NAME)).ok()?;
+        let mut numbers = content.split_whitespace().map(str::parse::<i64>);
+        let mut next = || numbers.next()?.ok();
+
+        let state = WindowState {
+            x: next()? as i32,
+            y: next()? as i32,
+            width: next()? as u32,
+            height: next()? as u32,
+        };
+        if state.width == 0 || state.height == 0 {
+            return None;
+        }
+        Some(state)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let dir = config_dir().ok_or_else(|| "no config directory".to_string())?;
+        let path = dir.join(FILE_NAME);
+        fs::write(
+            &path,
+            format!("{} {} {} {}\n", self.x, self.y, self.width, self.height),
+        )
+        .map_err(|e| format!("cannot write {}: {}", path.display(), e))
+    }
+}
@@ -1,12 +1,45 @@
 mod chip8;
 mod external_resources;
+mod options;
 
-use external_resources::rom;
+use chip8::{MemoryLayout, Variant};
+use external_resources::audio::ToneSettings;
+use external_resources::controller::ControllerMapping;
+use external_resources::display::{Display, DisplayKind, MemoryDisplay};
+use external_resources::input_source::{InputKind, InputSource};
+use external_resources::memory_layout;
+use external_resources::octo::{self, OctoOptions};
+use external_resources::palette::{self, Palette};
+use external_resources::patch;
+use external_resources::recording::{FramebufferRecording, RecordingFormat};
+use external_resources::rom::{self, Rom};
+use external_resources::rom_analysis;
+use external_resources::rom_browser::{self, RomBrowser};
+use external_resources::rom_database::RomInfo;
+use external_resources::screenshot;
+use external_resources::script::ScriptedInput;
+use external_resources::socket::SocketInput;
+use external_resources::terminal::TerminalDisplay;
+use external_resources::terminal_input::TerminalInput;
+use external_resources::wav::WavRecorder;
+use external_resources::Hotkey;
+use options::Options;
 
 use sdl2;
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+// The original interpreter ran roughly 500 instructions per second.
+const INSTRUCTIONS_PER_FRAME: u32 = 8;
+const FRAME_DURATION: Duration = Duration::from_micros(16_667);
+// Shown in the title until a ROM says otherwise.
+const PLATFORM_NAME: &str = "CHIP-8";
+// Speed steps in percent of normal speed.
+const SPEEDS: [u32; 7] = [25, 50, 75, 100, 150, 200, 400];
+const FAST_FORWARD_SPEED: u32 = 500;
 
 fn gen_env() -> Option<String> {
     for (key, val) in env::vars() {
@@ -18,26 +51,677 @@ fn gen_env() -> Option<String> {
 }
 
 fn main() -> Result<(), String> {
-    let cartridge_filename = gen_env().ok_or_else(|| "incorrect file path".to_string())?;
+    let mut args = env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("make-patch") {
+        let (original, modified, patch_path) = options::parse_make_patch(args.skip(1))?;
+        let read = |path: &str| fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e));
+        return patch::create(&read(&original)?, &read(&modified)?, Path::new(&patch_path));
+    }
+    if args.peek().map(String::as_str) == Some("unpack-octo") {
+        let cartridge = options::parse_unpack_octo(args.skip(1))?;
+        let bytes =
+            fs::read(&cartridge).map_err(|e| format!("cannot read {}: {}", cartridge, e))?;
+        println!("{}", octo::unpack(&cartridge, &bytes)?);
+        return Ok(());
+    }
 
-    let rom =
-        rom::read_rom(&cartridge_filename).or_else(|e| Err(format!("cannot read file: {}", e)))?;
+    let options = Options::parse(args)?;
+    let cartridge_filename = gen_env();
+    let tone = options.tone;
 
-    let sdl_context = sdl2::init()?;
+    let mut recorder = options.wav.as_ref().map(|_| WavRecorder::new(tone));
+
+    match options.headless {
+        Some(frames) => {
+            let cartridge_filename =
+                cartridge_filename.ok_or_else(|| "incorrect file path".to_string())?;
+            let mut game = boot(&cartridge_filename, options.patch.as_deref(), &options)?;
+            // There is no window in headless mode, so the SDL display falls back to memory.
+            let mut display: Box<dyn Display> = match options.display {
+                DisplayKind::Terminal => Box::new(TerminalDisplay::new()?),
+                DisplayKind::Sdl | DisplayKind::Memory => Box::new(MemoryDisplay::default()),
+            };
+            // Same for the SDL keyboard: nobody presses anything unless a script or a bot does.
+            let mut input: Box<dyn InputSource> = match &options.input {
+                InputKind::Sdl => Box::new(ScriptedInput::default()),
+                kind => open_input(kind)?,
+            };
+            let mut video = match &options.record {
+                Some(path) => {
+                    let path = Path::new(path);
+                    // Files without a known extension become GIFs.
+                    let format = RecordingFormat::from_path(path).unwrap_or(RecordingFormat::Gif);
+                    Some(FramebufferRecording::new(
+                        path,
+                        format,
+                        palette_for(&options, &cartridge_filename, &game)?,
+                        options.filters.clone(),
+                    ))
+                }
+                None => None,
+            };
+            run_headless(
+                &mut game,
+                frames,
+                display.as_mut(),
+                input.as_mut(),
+                recorder.as_mut(),
+                video.as_mut(),
+            )?;
+
+            if let Some(video) = video {
+                video.finish()?;
+            }
+
+            if let Some(path) = &options.screenshot {
+                let palette = palette_for(&options, &cartridge_filename, &game)?;
+                let image = screenshot::capture(
+                    game.cpu.framebuffer(),
+                    game.cpu.colors(),
+                    &palette,
+                    &options.filters,
+                    options.screenshot_scale,
+                );
+                screenshot::save_png(&image, Path::new(path))?;
+            }
+        }
+        None => run_sdl(cartridge_filename, &options, tone, recorder.as_mut())?,
+    }
 
-    let mut screen = external_resources::Screen::new(&sdl_context)?;
-    let mut input = external_resources::Input::new(&sdl_context)?;
+    if let (Some(path), Some(recorder)) = (&options.wav, recorder) {
+        recorder
+            .save(path)
+            .map_err(|e| format!("cannot write {}: {}", path, e))?;
+    }
 
-    let mut cpu = chip8::Chip8::new();
+    Ok(())
+}
+
+// A loaded ROM: the machine plus what the ROM database knows about it.
+struct Game {
+    cpu: chip8::Chip8,
+    info: Option<RomInfo>,
+    // The settings of an Octo cartridge.
+    octo: Option<OctoOptions>,
+    instructions_per_frame: u32,
+}
+
+// A freshly powered-on machine with the ROM loaded, also used for resets.
+// `patch` is the --patch file, which only belongs to ROM_PATH; other ROMs get the patch next to them.
+fn boot(cartridge_filename: &str, patch: Option<&str>, options: &Options) -> Result<Game, String> {
+    let Rom { bytes: rom, octo } = rom::read_rom(cartridge_filename)?;
+    // Patched ROMs are looked up as the game they're based on.
+    let info = RomInfo::lookup(&rom)?;
+
+    let patch_path = match patch {
+        Some(path) => Some(PathBuf::from(path)),
+        None => patch::patch_for_rom(cartridge_filename),
+    };
+    let rom = match patch_path {
+        Some(path) => {
+            if options.verbose {
+                eprintln!("{}: applying {}", cartridge_filename, path.display());
+            }
+            patch::apply_file(&rom, &path)?
+        }
+        None => rom,
+    };
+
+    let analysis = rom_analysis::analyse(&rom);
+
+    // The layout given on the command line wins over the one for the database's platform.
+    // The database has no platform for hi-res CHIP-8, those programs are listed as VIP ones.
+    // CHIP-8X ROMs are usually named .c8x.
+    // ROMs nothing decides for get the default from "memory-layouts", if it has one.
+    let default_layout = memory_layout::default_name()?;
+    let layout_name = options
+        .memory_layout
+        .as_deref()
+        .or_else(|| match info.as_ref().and_then(RomInfo::memory_layout) {
+            None if rom::is_chip8x(cartridge_filename) => Some("chip8x"),
+            None | Some("vip") if analysis.memory_layout.is_some() => analysis.memory_layout,
+            layout => layout,
+        })
+        .or(default_layout.as_deref());
+    let layout = match layout_name {
+        Some(name) => memory_layout::lookup(name)?,
+        None => MemoryLayout::default(),
+    };
+    if options.verbose {
+        eprintln!(
+            "{}: {} memory layout, {:?}",
+            cartridge_filename,
+            layout_name.unwrap_or("standard"),
+            layout
+        );
+    }
+    if rom.len() > layout.rom_size_limit() {
+        return Err(format!(
+            "{} is {} bytes, at most {} fit in memory from {:#05X}",
+            cartridge_filename,
+            rom.len(),
+            layout.rom_size_limit(),
+            layout.program_start
+        ));
+    }
+
+    // Quirks given on the command line win over a cartridge's settings, then the database,
+    // then guessing from the code.
+    let quirks = match (
+        options.quirks,
+        &octo,
+        info.as_ref().and_then(RomInfo::quirks),
+    ) {
+        (Some(quirks), _, _) => quirks,
+        (None, Some(octo), _) => {
+            let quirks = octo.quirks();
+            if options.verbose {
+                eprintln!(
+                    "{}: {:?} from the Octo cartridge",
+                    cartridge_filename, quirks
+                );
+            }
+            quirks
+        }
+        (None, None, Some(quirks)) => {
+            if let (true, Some(info)) = (options.verbose, &info) {
+                eprintln!(
+                    "{}: {} ({}) from the ROM database",
+                    cartridge_filename,
+                    info.description(),
+                    info.platform.as_deref().unwrap_or("unknown platform")
+                );
+            }
+            quirks
+        }
+        (None, None, None) => {
+            if options.verbose {
+                eprintln!(
+                    "{}: looks like {} ({} confidence), {:?}",
+                    cartridge_filename,
+                    analysis.platform,
+                    analysis.confidence.name(),
+                    analysis.quirks
+                );
+                for note in &analysis.notes {
+                    eprintln!("  {}", note);
+                }
+            }
+            analysis.quirks
+        }
+    };
+    let instructions_per_frame = options
+        .instructions_per_frame
+        .or_else(|| octo.as_ref().and_then(|octo| octo.tickrate))
+        .or_else(|| info.as_ref().and_then(|info| info.tickrate))
+        .unwrap_or(INSTRUCTIONS_PER_FRAME);
+
+    let mut cpu = chip8::Chip8::new(layout);
+    if let Some(font) = &options.font {
+        cpu.set_font(font);
+    }
+    cpu.set_quirks(quirks);
     cpu.load_into_memory(&rom);
+    Ok(Game {
+        cpu,
+        info,
+        octo,
+        instructions_per_frame,
+    })
+}
+
+// One emulated 60 Hz frame: a batch of instructions, then the timers and the screen.
+fn run_frame(
+    game: &mut Game,
+    keypad: [bool; 16],
+    second_keypad: [bool; 16],
+    display: &mut dyn Display,
+) -> Result<(), String> {
+    let cpu = &mut game.cpu;
+    cpu.set_second_keypad(second_keypad);
+    for _ in 0..game.instructions_per_frame {
+        cpu.tick(keypad);
+    }
+
+    if let Some(val) = cpu.vblank() {
+        display.draw(val)?;
+        display.set_colors(cpu.colors());
+    }
+    Ok(())
+}
+
+// Runs up to a fixed number of frames as fast as possible, with no window and no sound card.
+fn run_headless(
+    game: &mut Game,
+    frames: u64,
+    display: &mut dyn Display,
+    input: &mut dyn InputSource,
+    mut recorder: Option<&mut WavRecorder>,
+    mut video: Option<&mut FramebufferRecording>,
+) -> Result<(), String> {
+    for _ in 0..frames {
+        let keypad = match input.poll() {
+            Ok(keypad) => keypad,
+            Err(_) => break,
+        };
+
+        run_frame(game, keypad, input.second_keypad(), display)?;
+        display.end_frame();
+        display.present()?;
+        if let Some(reason) = game.cpu.halted() {
+            return Err(format!("the program halted: {}", reason));
+        }
+
+        if let Some(recorder) = recorder.as_mut() {
+            recorder.record_frame(game.cpu.is_sound_playing());
+        }
+        if let Some(video) = video.as_mut() {
+            video.push(game.cpu.framebuffer(), game.cpu.colors())?;
+        }
+    }
+
+    Ok(())
+}
 
+// Without a ROM this starts with the ROM menu instead of a game.
+fn run_sdl(
+    mut cartridge_filename: Option<String>,
+    options: &Options,
+    tone: ToneSettings,
+    mut recorder: Option<&mut WavRecorder>,
+) -> Result<(), String> {
+    let sdl_context = sdl2::init()?;
+    // Dropping or picking another ROM leaves the --patch file behind.
+    let mut patch = options.patch.as_deref();
+    let mut game = match &cartridge_filename {
+        Some(path) => Some(boot(path, patch, options)?),
+        None => None,
+    };
+
+    let loaded = cartridge_filename.as_deref().zip(game.as_ref());
+    let mut display: Box<dyn Display> = match options.display {
+        DisplayKind::Sdl => {
+            let settings = external_resources::ScreenSettings {
+                title: match loaded {
+                    Some((path, game)) => window_title(path, game, 100),
+                    None => PLATFORM_NAME.to_string(),
+                },
+                persistence: options.persistence,
+                palette: match loaded {
+                    Some((path, game)) => palette_for(options, path, game)?,
+                    None => options.palette.clone().unwrap_or_default(),
+                },
+                palette_path: cartridge_filename.as_deref().map(palette::rom_palette_path),
+                filters: options.filters.clone(),
+                integer_scale: options.integer_scale,
+                rom_name: cartridge_filename
+                    .as_deref()
+                    .map(rom_name)
+                    .unwrap_or_default(),
+                screenshot_scale: options.screenshot_scale,
+                recording_format: options.recording_format,
+                show_stats: options.show_fps,
+            };
+            Box::new(external_resources::Screen::new(&sdl_context, settings)?)
+        }
+        DisplayKind::Terminal => Box::new(TerminalDisplay::new()?),
+        DisplayKind::Memory => Box::new(MemoryDisplay::default()),
+    };
+    let mut input: Box<dyn InputSource> = match &options.input {
+        InputKind::Sdl => {
+            let mapping = match loaded {
+                Some((path, game)) => controller_mapping(path, game)?,
+                None => ControllerMapping::default(),
+            };
+            let bindings = external_resources::hotkeys::HotkeyBindings::load()?;
+            Box::new(external_resources::Input::new(
+                &sdl_context,
+                mapping,
+                bindings,
+            )?)
+        }
+        kind => open_input(kind)?,
+    };
+
+    let mut buzzer = external_resources::Buzzer::new(&sdl_context, tone)?;
+
+    let mut browser = match loaded {
+        Some((path, game)) => {
+            if let Some(info) = &game.info {
+                show_message(display.as_mut(), &info.description());
+            }
+            // Not being able to remember it is no reason to refuse the game.
+            let _ = rom_browser::add_recent(path);
+            None
+        }
+        // The menu is drawn by the on-screen display, other displays can't show it.
+        None if display.osd().is_none() => return Err("incorrect file path".to_string()),
+        None => Some(RomBrowser::new(&options.rom_dir)?),
+    };
+
+    let mut next_frame = Instant::now();
+    // Frames and instructions run since the counter was last updated.
+    let mut stats_start = Instant::now();
+    let mut stats = (0, 0);
+    let mut paused = false;
+    let mut speed = 100;
+    // Emulated frames are run in whole numbers, the remainder carries over to the next host frame.
+    let mut frame_budget = 0.0;
+    let mut watcher = match &cartridge_filename {
+        Some(path) if options.watch => Some(rom::RomWatcher::new(path)),
+        _ => None,
+    };
     while let Ok(keypad) = input.poll() {
-        let tick = cpu.tick(keypad);
-        if let Some(val) = tick {
-            screen.draw(val)?;
+        let picked = browser.as_mut().and_then(|browser| browser.update(keypad));
+        if let Some(path) = input.dropped_file().or(picked) {
+            match boot(&path, None, options) {
+                Ok(booted) => {
+                    show_rom(
+                        display.as_mut(),
+                        input.as_mut(),
+                        &path,
+                        &booted,
+                        options,
+                        speed,
+                    )?;
+                    game = Some(booted);
+                    browser = None;
+                    patch = None;
+                    if options.watch {
+                        watcher = Some(rom::RomWatcher::new(&path));
+                    }
+                    cartridge_filename = Some(path);
+                }
+                Err(e) => show_message(display.as_mut(), &e),
+            }
+        }
+        if let (Some(browser), Some(osd)) = (&browser, display.osd()) {
+            osd.menu = Some(browser.menu());
+        }
+
+        if let (Some(path), Some(watcher)) = (&cartridge_filename, watcher.as_mut()) {
+            if watcher.changed() {
+                // The assembler might still be writing, a failed reload is retried on the next change.
+                match boot(path, patch, options) {
+                    Ok(reloaded) => {
+                        game = Some(reloaded);
+                        show_message(display.as_mut(), "ROM reloaded");
+                    }
+                    Err(e) => show_message(display.as_mut(), &e),
+                }
+            }
+        }
+
+        let mut frame_advance = false;
+        for &hotkey in input.hotkeys() {
+            match hotkey {
+                Hotkey::ToggleMute => {
+                    let message = if buzzer.toggle_mute() {
+                        "Sound muted"
+                    } else {
+                        "Sound on"
+                    };
+                    show_message(display.as_mut(), message);
+                }
+                Hotkey::Pause => {
+                    paused = !paused;
+                    if let Some(osd) = display.osd() {
+                        osd.paused = paused;
+                    }
+                }
+                Hotkey::FrameAdvance => frame_advance = paused,
+                Hotkey::Reset => {
+                    // Like a reload, a ROM that is being rewritten shouldn't close the emulator.
+                    if let Some(path) = &cartridge_filename {
+                        match boot(path, patch, options) {
+                            Ok(booted) => {
+                                game = Some(booted);
+                                show_message(display.as_mut(), "Reset");
+                            }
+                            Err(e) => show_message(display.as_mut(), &e),
+                        }
+                    }
+                }
+                Hotkey::SpeedUp | Hotkey::SpeedDown => {
+                    speed = next_speed(speed, hotkey == Hotkey::SpeedUp);
+                    if let (Some(path), Some(game)) = (&cartridge_filename, &game) {
+                        display.set_title(&window_title(path, game, speed))?;
+                    }
+                    show_message(display.as_mut(), &format!("Speed {}%", speed));
+                }
+                _ => display.hotkey(hotkey)?,
+            }
+        }
+
+        let frames = if paused {
+            frame_advance as u32
+        } else {
+            let speed = if input.is_held(Hotkey::FastForward) {
+                FAST_FORWARD_SPEED
+            } else {
+                speed
+            };
+            frame_budget += speed as f32 / 100.0;
+            let frames = frame_budget as u32;
+            frame_budget -= frames as f32;
+            frames
+        };
+
+        if let Some(game) = game.as_mut() {
+            let was_halted = game.cpu.halted().is_some();
+            for _ in 0..frames {
+                run_frame(game, keypad, input.second_keypad(), display.as_mut())?;
+                display.end_frame();
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.record_frame(game.cpu.is_sound_playing());
+                }
+            }
+            // The window stays open so the last frame can be looked at, a reset starts over.
+            if let (false, Some(reason)) = (was_halted, game.cpu.halted()) {
+                show_message(display.as_mut(), &format!("Halted: {}", reason));
+            }
+        }
+        display.present()?;
+
+        stats.0 += 1;
+        stats.1 += game
+            .as_ref()
+            .map_or(0, |game| frames * game.instructions_per_frame);
+        if stats_start.elapsed() >= Duration::from_secs(1) {
+            if let Some(osd) = display.osd() {
+                osd.stats = stats;
+            }
+            stats = (0, 0);
+            stats_start = Instant::now();
+        }
+
+        // A paused game would otherwise keep beeping forever, the sound timer doesn't count down.
+        let playing = game
+            .as_ref()
+            .is_some_and(|game| game.cpu.is_sound_playing())
+            && !paused;
+        buzzer.set_playing(playing);
+        input.rumble(playing);
+
+        next_frame += FRAME_DURATION;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            // We're running behind, don't try to catch up with a burst of frames.
+            next_frame = now;
         }
-        thread::sleep(Duration::from_millis(2));
     }
 
     Ok(())
 }
+
+// Points the display and the controllers at a ROM that was just loaded from the menu or dropped on the window.
+fn show_rom(
+    display: &mut dyn Display,
+    input: &mut dyn InputSource,
+    cartridge_filename: &str,
+    game: &Game,
+    options: &Options,
+    speed: u32,
+) -> Result<(), String> {
+    display.set_rom(
+        &rom_name(cartridge_filename),
+        palette_for(options, cartridge_filename, game)?,
+        palette::rom_palette_path(cartridge_filename),
+    );
+    display.set_title(&window_title(cartridge_filename, game, speed))?;
+    input.set_mapping(controller_mapping(cartridge_filename, game)?);
+    if let Some(osd) = display.osd() {
+        osd.menu = None;
+    }
+    if let Some(info) = &game.info {
+        show_message(display, &info.description());
+    }
+    let _ = rom_browser::add_recent(cartridge_filename);
+    Ok(())
+}
+
+fn show_message(display: &mut dyn Display, message: &str) {
+    if let Some(osd) = display.osd() {
+        osd.show_message(message);
+    }
+}
+
+// The next step up or down from the current speed, staying at the ends.
+fn next_speed(speed: u32, faster: bool) -> u32 {
+    let index = SPEEDS.iter().position(|&s| s == speed).unwrap_or(0);
+    let index = if faster {
+        (index + 1).min(SPEEDS.len() - 1)
+    } else {
+        index.saturating_sub(1)
+    };
+    SPEEDS[index]
+}
+
+fn rom_name(cartridge_filename: &str) -> String {
+    Path::new(cartridge_filename)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| cartridge_filename.to_string())
+}
+
+// "Pong - CHIP-8 - 100%", with the file name for ROMs the database doesn't know.
+// The platform is the variant being emulated, or the database's platform for plain CHIP-8.
+fn window_title(cartridge_filename: &str, game: &Game, speed_percent: u32) -> String {
+    let name = match &game.info {
+        Some(info) => info.title.clone(),
+        None => rom_name(cartridge_filename),
+    };
+    let platform = match game.cpu.variant() {
+        Variant::Chip8 => game
+            .info
+            .as_ref()
+            .and_then(RomInfo::platform_name)
+            .unwrap_or(PLATFORM_NAME),
+        variant => variant.name(),
+    };
+    format!("{} - {} - {}%", name, platform, speed_percent)
+}
+
+// A palette given on the command line wins over the one remembered for the ROM,
+// which wins over the colours from the ROM database.
+fn palette_for(
+    options: &Options,
+    cartridge_filename: &str,
+    game: &Game,
+) -> Result<Palette, String> {
+    if let Some(palette) = &options.palette {
+        return Ok(palette.clone());
+    }
+    Ok(Palette::for_rom(cartridge_filename)?
+        .or_else(|| {
+            let octo = game.octo.as_ref()?;
+            octo.palette(&rom_name(cartridge_filename))
+        })
+        .or_else(|| game.info.as_ref().and_then(RomInfo::palette))
+        .unwrap_or_default())
+}
+
+// A mapping file next to the ROM wins over the database hints.
+fn controller_mapping(cartridge_filename: &str, game: &Game) -> Result<ControllerMapping, String> {
+    Ok(ControllerMapping::for_rom(cartridge_filename)?
+        .or_else(|| game.info.as_ref().and_then(RomInfo::controller_mapping))
+        .unwrap_or_default())
+}
+
+// Everything except the SDL keyboard, which needs the SDL context.
+fn open_input(kind: &InputKind) -> Result<Box<dyn InputSource>, String> {
+    match kind {
+        InputKind::Sdl => Err("SDL input needs a window".to_string()),
+        InputKind::Terminal => Ok(Box::new(TerminalInput::new()?)),
+        InputKind::Script(path) => Ok(Box::new(ScriptedInput::load(path)?)),
+        InputKind::Socket(port) => Ok(Box::new(SocketInput::new(*port)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(rom: &[u8]) -> Game {
+        let mut cpu = chip8::Chip8::new(MemoryLayout::default());
+        cpu.load_into_memory(rom);
+        Game {
+            cpu,
+            info: None,
+            octo: None,
+            instructions_per_frame: INSTRUCTIONS_PER_FRAME,
+        }
+    }
+
+    #[test]
+    fn draws_font_digit_once_per_frame() {
+        // V0 = 0, I = digit 0, draw it at (0, 0), then loop forever.
+        let mut game = game(&[0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06]);
+        let mut display = MemoryDisplay::default();
+
+        run_frame(&mut game, [false; 16], [false; 16], &mut display).unwrap();
+        run_frame(&mut game, [false; 16], [false; 16], &mut display).unwrap();
+
+        // Nothing changed in the second frame, so it wasn't handed over again.
+        assert_eq!(display.draw_count, 1);
+        let frame = display.frame.unwrap();
+        assert_eq!(frame.len(), 32);
+        for (row, &bits) in frame.iter().zip(chip8::CHIP8_FONTSET[..5].iter()) {
+            for (x, &pixel) in row[..8].iter().enumerate() {
+                assert_eq!(pixel, (bits >> (7 - x)) & 1, "pixel {}", x);
+            }
+        }
+        assert!(frame[5..].iter().all(|row| row.iter().all(|&p| p == 0)));
+    }
+
+    #[test]
+    fn clear_screen_erases_the_frame() {
+        // Draw digit 0, clear the screen, loop.
+        let mut game = game(&[0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x00, 0xE0, 0x12, 0x08]);
+        let mut display = MemoryDisplay::default();
+
+        run_frame(&mut game, [false; 16], [false; 16], &mut display).unwrap();
+
+        let frame = display.frame.unwrap();
+        assert!(frame.iter().all(|row| row.iter().all(|&p| p == 0)));
+    }
+
+    #[test]
+    fn runaway_recursion_halts_instead_of_panicking() {
+        // A subroutine that calls itself forever.
+        let mut game = game(&[0x22, 0x00]);
+        let mut display = MemoryDisplay::default();
+
+        // The 17th call overflows the 16 levels of the default layout.
+        for _ in 0..3 {
+            run_frame(&mut game, [false; 16], [false; 16], &mut display).unwrap();
+        }
+
+        assert_eq!(
+            game.cpu.halted(),
+            Some("stack overflow: more than 16 nested calls at 0x200")
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use crate::chip8::{Font, Quirks};
+use crate::external_resources::audio::{ToneSettings, Waveform};
+use crate::external_resources::display::DisplayKind;
+use crate::external_resources::filters::Filter;
+use crate::external_resources::font;
+use crate::external_resources::input_source::InputKind;
+use crate::external_resources::palette::Palette;
+use crate::external_resources::persistence::Persistence;
+use crate::external_resources::recording::RecordingFormat;
+
+const USAGE: &str = "usage: rusty-nes-emulator [--display sdl|terminal|memory] \
+[--input sdl|terminal|script:<path>|socket:<port>] \
+[--display-wait] [--shift-vy] [--load-store-increment] [--jump-vx] [--ipf <n>] [--verbose] \
+[--persistence off|blend|linear:<frames>|exponential:<frames>] \
+[--palette <name>|<path>] \
+[--filter scale2x|scale3x|epx|xbr|scanlines|grid[,...]] [--integer-scale] \
+[--screenshot-scale <factor>] [--screenshot <path>] \
+[--record-format gif|apng] [--record <path>] [--show-fps] [--watch] [--rom-dir <dir>] \
+[--patch <path>] [--memory-layout standard|vip|eti660|hires|chip8x|<name>] \
+[--font vip|dream6800|eti660|schip|octo|<path>] \
+[--buzzer-waveform square|sine|triangle] [--buzzer-frequency <Hz>] [--buzzer-volume <0.0-1.0>] \
+[--wav <path>] [--headless <frames>]
+       rusty-nes-emulator make-patch <original> <modified> <patch.ips|patch.bps>
+       rusty-nes-emulator unpack-octo <cartridge.gif>";
+
+pub struct Options {
+    pub display: DisplayKind,
+    pub input: InputKind,
+    // None lets the ROM database decide.
+    pub quirks: Option<Quirks>,
+    // Instructions per frame. None lets the ROM database decide.
+    pub instructions_per_frame: Option<u32>,
+    pub persistence: Persistence,
+    pub palette: Option<Palette>,
+    pub filters: Vec<Filter>,
+    pub integer_scale: bool,
+    pub screenshot_scale: usize,
+    // Headless runs write the final screen here.
+    pub screenshot: Option<String>,
+    pub recording_format: RecordingFormat,
+    // Headless runs record every frame into this file.
+    pub record: Option<String>,
+    pub show_fps: bool,
+    // Reload the ROM whenever the file changes.
+    pub watch: bool,
+    // Where the ROM menu looks when no ROM is given.
+    pub rom_dir: String,
+    // Explain on stderr how the quirks for a ROM were chosen.
+    pub verbose: bool,
+    // Applied to the ROM instead of a same-named .ips or .bps next to it.
+    pub patch: Option<String>,
+    // A built-in memory layout or one from the config directory. None picks one for the ROM.
+    pub memory_layout: Option<String>,
+    pub font: Option<Font>,
+    pub tone: ToneSettings,
+    // Writes the buzzer to a WAV file, timed by emulated frames.
+    pub wav: Option<String>,
+    // Runs this many frames without a window or sound card, then exits.
+    pub headless: Option<u64>,
+}
+
+impl Options {
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut options = Options {
+            display: DisplayKind::Sdl,
+            input: InputKind::Sdl,
+            quirks: None,
+            instructions_per_frame: None,
+            persistence: Persistence::Off,
+            palette: None,
+            filters: Vec::new(),
+            integer_scale: false,
+            screenshot_scale: 1,
+            screenshot: None,
+            recording_format: RecordingFormat::Gif,
+            record: None,
+            show_fps: false,
+            watch: false,
+            rom_dir: ".".to_string(),
+            verbose: false,
+            patch: None,
+            memory_layout: None,
+            font: None,
+            tone: ToneSettings::default(),
+            wav: None,
+            headless: None,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--display" => {
+                    let name = value(&mut args, &arg)?;
+                    options.display = DisplayKind::from_name(&name)
+                        .ok_or_else(|| format!("unknown display: {}\n{}", name, USAGE))?;
+                }
+                "--input" => {
+                    let name = value(&mut args, &arg)?;
+                    options.input = InputKind::from_name(&name)
+                        .ok_or_else(|| format!("unknown input: {}\n{}", name, USAGE))?;
+                }
+                "--display-wait" => quirks(&mut options).display_wait = true,
+                "--shift-vy" => quirks(&mut options).shift_reads_vy = true,
+                "--load-store-increment" => quirks(&mut options).load_store_increments_i = true,
+                "--jump-vx" => quirks(&mut options).jump_uses_vx = true,
+                "--ipf" => {
+                    let count = value(&mut args, &arg)?;
+                    options.instructions_per_frame =
+                        Some(count.parse().ok().filter(|&n| n > 0).ok_or_else(|| {
+                            format!("invalid instructions per frame: {}\n{}", count, USAGE)
+                        })?);
+                }
+                "--verbose" | "-v" => options.verbose = true,
+                "--persistence" => {
+                    let name = value(&mut args, &arg)?;
+                    options.persistence = Persistence::from_name(&name)
+                        .ok_or_else(|| format!("unknown persistence: {}\n{}", name, USAGE))?;
+                }
+                "--palette" => {
+                    let name = value(&mut args, &arg)?;
+                    options.palette = Some(Palette::find(&name)?);
+                }
+                "--filter" => {
+                    let names = value(&mut args, &arg)?;
+                    options.filters = Filter::parse_chain(&names)
+                        .ok_or_else(|| format!("unknown filter in: {}\n{}", names, USAGE))?;
+                }
+                "--integer-scale" => options.integer_scale = true,
+                "--screenshot-scale" => {
+                    let factor = value(&mut args, &arg)?;
+                    options.screenshot_scale =
+                        factor.parse().ok().filter(|&f| f > 0).ok_or_else(|| {
+                            format!("invalid screenshot scale: {}\n{}", factor, USAGE)
+                        })?;
+                }
+                "--screenshot" => options.screenshot = Some(value(&mut args, &arg)?),
+                "--record-format" => {
+                    let name = value(&mut args, &arg)?;
+                    options.recording_format = RecordingFormat::from_name(&name)
+                        .ok_or_else(|| format!("unknown recording format: {}\n{}", name, USAGE))?;
+                }
+                "--record" => options.record = Some(value(&mut args, &arg)?),
+                "--show-fps" => options.show_fps = true,
+                "--watch" => options.watch = true,
+                "--rom-dir" => options.rom_dir = value(&mut args, &arg)?,
+                "--patch" => options.patch = Some(value(&mut args, &arg)?),
+                "--memory-layout" => options.memory_layout = Some(value(&mut args, &arg)?),
+                "--font" => {
+                    let name = value(&mut args, &arg)?;
+                    options.font = Some(font::find(&name)?);
+                }
+                "--buzzer-waveform" => {
+                    let name = value(&mut args, &arg)?;
+                    options.tone.waveform = Waveform::from_name(&name)
+                        .ok_or_else(|| format!("unknown buzzer waveform: {}\n{}", name, USAGE))?;
+                }
+                "--buzzer-frequency" => {
+                    let hz = value(&mut args, &arg)?;
+                    options.tone.frequency =
+                        hz.parse().ok().filter(|&f| f > 0.0).ok_or_else(|| {
+                            format!("invalid buzzer frequency: {}\n{}", hz, USAGE)
+                        })?;
+                }
+                "--buzzer-volume" => {
+                    let volume = value(&mut args, &arg)?;
+                    let parsed: f32 = volume
+                        .parse()
+                        .map_err(|_| format!("invalid buzzer volume: {}\n{}", volume, USAGE))?;
+                    options.tone.volume = parsed.clamp(0.0, 1.0);
+                }
+                "--wav" => options.wav = Some(value(&mut args, &arg)?),
+                "--headless" => {
+                    let frames = value(&mut args, &arg)?;
+                    options.headless =
+                        Some(frames.parse().map_err(|_| {
+                            format!("invalid number of frames: {}\n{}", frames, USAGE)
+                        })?);
+                }
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                _ => return Err(format!("unknown argument: {}\n{}", arg, USAGE)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+// Any quirk given on the command line replaces the automatic choice as a whole.
+fn quirks(options: &mut Options) -> &mut Quirks {
+    options.quirks.get_or_insert_with(Quirks::default)
+}
+
+// make-patch <original> <modified> <patch>
+pub fn parse_make_patch<I: Iterator<Item = String>>(
+    mut args: I,
+) -> Result<(String, String, String), String> {
+    match (args.next(), args.next(), args.next(), args.next()) {
+        (Some(original), Some(modified), Some(patch), None) => Ok((original, modified, patch)),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+// unpack-octo <cartridge>
+pub fn parse_unpack_octo<I: Iterator<Item = String>>(mut args: I) -> Result<String, String> {
+    match (args.next(), args.next()) {
+        (Some(cartridge), None) => Ok(cartridge),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("{} needs a value\n{}", flag, USAGE))
+}